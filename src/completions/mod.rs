@@ -0,0 +1,372 @@
+//! Shell completion.
+//!
+//! `ff completions SHELL` prints a small script for the shell, which calls
+//! back into `ff __complete N -- WORDS` whenever the user presses tab:
+//! `WORDS` is the command line up to the cursor and `N` the index of the
+//! word being completed. The candidates come from the flag and command
+//! tables in [`args`] and from the module registry, so they never get out
+//! of date with the binary. Each candidate is printed on its own line,
+//! followed by a tab and a description if it has one.
+use crate::config::args::{Args, Flag, COMMANDS, FLAGS};
+use crate::config::{layers, Config};
+use crate::modules::{self, REGISTRY};
+use crate::output::FORMATS;
+use std::fs;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Elvish,
+    Powershell,
+}
+
+/// The shells `ff completions` accepts, as they are written there.
+pub const SHELLS: &[&str] = &["bash", "zsh", "fish", "elvish", "powershell"];
+
+impl Shell {
+    pub fn parse(name: &str) -> Option<Shell> {
+        match name.to_ascii_lowercase().as_str() {
+            "bash" => Some(Shell::Bash),
+            "zsh" => Some(Shell::Zsh),
+            "fish" => Some(Shell::Fish),
+            "elvish" => Some(Shell::Elvish),
+            "powershell" | "pwsh" => Some(Shell::Powershell),
+            _ => None,
+        }
+    }
+    /// The completion script, ready to be sourced or saved where the shell
+    /// looks for completions.
+    pub fn script(&self) -> &'static str {
+        match self {
+            Shell::Bash => BASH,
+            Shell::Zsh => ZSH,
+            Shell::Fish => FISH,
+            Shell::Elvish => ELVISH,
+            Shell::Powershell => POWERSHELL,
+        }
+    }
+}
+
+/// The candidates for `words[current]`, or for an empty word if the shell
+/// left it out. `words[0]` is the program itself.
+pub fn complete(words: &[String], current: usize) -> Vec<(String, String)> {
+    let current = current.clamp(1, words.len().max(1));
+    let word = words.get(current).map_or("", String::as_str);
+    let mut operands = Vec::new();
+    let mut pending: Option<&Flag> = None;
+    let mut operands_only = false;
+    for previous in words.get(1..current).unwrap_or_default() {
+        if pending.take().is_some() {
+            continue;
+        }
+        if operands_only || !previous.starts_with('-') || previous == "-" {
+            operands.push(previous.as_str());
+        } else if previous == "--" {
+            operands_only = true;
+        } else if let Some(long) = previous.strip_prefix("--") {
+            pending = FLAGS.iter().find(|flag| flag.long == long).filter(|flag| takes_next_word(flag));
+        } else {
+            // in `-hw` the next word is the value of `-w`, in `-w0.5` it is not
+            let shorts = &previous[1..];
+            for (index, short) in shorts.char_indices() {
+                match FLAGS.iter().find(|flag| flag.short == Some(short)) {
+                    Some(flag) if flag.value.is_some() => {
+                        if index + short.len_utf8() == shorts.len() && takes_next_word(flag) {
+                            pending = Some(flag);
+                        }
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    let mut candidates = match pending {
+        Some(flag) => flag_values(flag.arg, word),
+        None if operands_only || !word.starts_with('-') => command_values(&operands),
+        None => match word.strip_prefix("--").and_then(|long| long.split_once('=')) {
+            Some((long, value)) => match FLAGS.iter().find(|flag| flag.long == long) {
+                Some(flag) => flag_values(flag.arg, value)
+                    .into_iter()
+                    .map(|(value, description)| (format!("--{}={}", long, value), description))
+                    .collect(),
+                None => Vec::new(),
+            },
+            None => FLAGS
+                .iter()
+                .map(|flag| (format!("--{}", flag.long), summary(flag.description)))
+                .collect(),
+        },
+    };
+    candidates.retain(|(candidate, _)| candidate.starts_with(word));
+    candidates
+}
+
+/// Flags whose value is the next word. Optional values need `=`.
+fn takes_next_word(flag: &Flag) -> bool {
+    flag.value.is_some() && flag.default.is_none()
+}
+
+/// The first line of a description from the flag and command tables.
+fn summary(description: &str) -> String {
+    description.lines().next().unwrap_or_default().to_string()
+}
+
+/// Values for a flag, given what has been typed of it so far.
+fn flag_values(arg: Args, value: &str) -> Vec<(String, String)> {
+    match arg {
+        Args::Align => words(&["left", "right", "center"]),
+        Args::DefaultConfig => words(&["json", "jsonc", "toml", "yaml"]),
+        Args::Format => words(FORMATS),
+        Args::Modules => list(value, REGISTRY.iter().map(|info| info.name)),
+        // `Disks=` first, then the stats of that module
+        Args::Stats => match value.split_once('=') {
+            Some((module, stats)) => match modules::find(module) {
+                Some(info) => list(stats, info.stat_names())
+                    .into_iter()
+                    .map(|(stats, description)| (format!("{}={}", module, stats), description))
+                    .collect(),
+                None => Vec::new(),
+            },
+            None => REGISTRY
+                .iter()
+                .map(|info| (format!("{}=", info.name), String::new()))
+                .collect(),
+        },
+        Args::ConfigFile => paths(value),
+        _ => Vec::new(),
+    }
+}
+
+/// Commands that continue `operands`, or the values of the operand that
+/// comes next.
+fn command_values(operands: &[&str]) -> Vec<(String, String)> {
+    let mut candidates: Vec<(String, String)> = Vec::new();
+    for command in COMMANDS {
+        if command.words.len() > operands.len() && command.words[..operands.len()] == *operands {
+            let word = command.words[operands.len()];
+            if !candidates.iter().any(|(candidate, _)| candidate == word) {
+                let description = match command.words.len() == operands.len() + 1 {
+                    true => summary(command.description),
+                    false => String::new(),
+                };
+                candidates.push((word.to_string(), description));
+            }
+        }
+    }
+    let command = COMMANDS
+        .iter()
+        .filter(|command| command.words.len() <= operands.len() && command.words[..] == operands[..command.words.len()])
+        .max_by_key(|command| command.words.len());
+    if let Some(command) = command {
+        match command.operands.get(operands.len() - command.words.len()) {
+            Some(&"NAME") => candidates.extend(words(&REGISTRY.iter().map(|info| info.name).collect::<Vec<_>>())),
+            Some(&"SHELL") => candidates.extend(words(SHELLS)),
+            Some(&"KEY") => {
+                let defaults = serde_json::to_value(Config::default()).expect("the default config always serializes");
+                candidates.extend(layers::leaves(&defaults).into_iter().map(|(key, _)| (key, String::new())));
+            }
+            _ => {}
+        }
+    }
+    candidates
+}
+
+fn words(words: &[&str]) -> Vec<(String, String)> {
+    words.iter().map(|word| (word.to_string(), String::new())).collect()
+}
+
+/// Completes the last item of a comma separated list, leaving out the
+/// items that are already in it.
+fn list<'a>(value: &str, items: impl Iterator<Item = &'a str>) -> Vec<(String, String)> {
+    let done = value.rfind(',').map_or("", |index| &value[..=index]);
+    let given: Vec<&str> = done.split(',').map(str::trim).collect();
+    items
+        .filter(|item| !given.contains(item))
+        .map(|item| (format!("{}{}", done, item), String::new()))
+        .collect()
+}
+
+/// Files and directories starting with `value`. Directories end with `/`
+/// so that the shell does not add a space after them.
+fn paths(value: &str) -> Vec<(String, String)> {
+    let (dir, prefix) = match value.rfind('/') {
+        Some(index) => (&value[..=index], &value[index + 1..]),
+        None => ("", value),
+    };
+    let Ok(entries) = fs::read_dir(if dir.is_empty() { "." } else { dir }) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<(String, String)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let slash = if entry.path().is_dir() { "/" } else { "" };
+            Some((format!("{}{}{}", dir, name, slash), String::new()))
+        })
+        .collect();
+    paths.sort();
+    paths
+}
+
+const BASH: &str = r#"# bash completion for ff
+# Load it with `source <(ff completions bash)` or save it as
+# ~/.local/share/bash-completion/completions/ff
+_ff() {
+    local line="${COMP_LINE:0:COMP_POINT}"
+    local cur="${line##*[[:space:]]}"
+    local -a words
+    read -ra words <<< "${line%"$cur"}"
+    local IFS=$'\n'
+    COMPREPLY=($(ff __complete "${#words[@]}" -- "${words[@]}" "$cur" 2>/dev/null | cut -f1))
+    # bash splits words at `=` and `:`, so only the part after them is replaced
+    local prefix="${cur%"${cur##*[=:]}"}"
+    COMPREPLY=("${COMPREPLY[@]#"$prefix"}")
+    if [[ ${#COMPREPLY[@]} -eq 1 && $COMPREPLY == *[=,/] ]]; then
+        compopt -o nospace
+    fi
+}
+complete -F _ff ff
+"#;
+
+const ZSH: &str = r#"#compdef ff
+# zsh completion for ff
+# Load it with `source <(ff completions zsh)` or save it as _ff in a
+# directory on $fpath
+_ff() {
+    local -a lines closed closed_display open open_display
+    local line value description display
+    lines=("${(@f)$(ff __complete $((CURRENT - 1)) -- "${(@)words[1,CURRENT]}" 2>/dev/null)}")
+    for line in $lines; do
+        value=${line%%$'\t'*}
+        description=${line#*$'\t'}
+        display=$value
+        [[ $description != $line ]] && display="$value  -- $description"
+        if [[ $value == *[=,/] ]]; then
+            open+=("$value")
+            open_display+=("$display")
+        else
+            closed+=("$value")
+            closed_display+=("$display")
+        fi
+    done
+    (( $#closed )) && compadd -l -d closed_display -- "${closed[@]}"
+    (( $#open )) && compadd -S '' -l -d open_display -- "${open[@]}"
+}
+if [ "$funcstack[1]" = "_ff" ]; then
+    _ff "$@"
+else
+    compdef _ff ff
+fi
+"#;
+
+const FISH: &str = r#"# fish completion for ff
+# Load it with `ff completions fish | source` or save it as
+# ~/.config/fish/completions/ff.fish
+function __ff_complete
+    set -l previous (commandline -opc)
+    ff __complete (count $previous) -- $previous (commandline -ct) 2>/dev/null
+end
+complete -c ff -f -a '(__ff_complete)'
+"#;
+
+const ELVISH: &str = r#"# elvish completion for ff
+# Load it with `eval (ff completions elvish | slurp)` or add that line to
+# ~/.config/elvish/rc.elv
+use re
+use str
+set edit:completion:arg-completer[ff] = {|@words|
+    ff __complete (- (count $words) 1) -- $@words 2>/dev/null | each {|line|
+        var value = (str:split "\t" $line | take 1)
+        var suffix = ' '
+        if (re:match '[=,/]$' $value) {
+            set suffix = ''
+        }
+        edit:complex-candidate $value &code-suffix=$suffix
+    }
+}
+"#;
+
+const POWERSHELL: &str = r#"# Powershell completion for ff
+# Load it with `ff completions powershell | Out-String | Invoke-Expression`
+# or add that line to $PROFILE
+Register-ArgumentCompleter -Native -CommandName ff -ScriptBlock {
+    param($wordToComplete, $commandAst, $cursorPosition)
+    $words = @($commandAst.CommandElements |
+        Where-Object { $_.Extent.StartOffset -lt $cursorPosition } |
+        ForEach-Object { $_.Extent.Text })
+    $current = $words.Count
+    if ($wordToComplete) {
+        $current -= 1
+    }
+    & ff __complete $current -- @words 2>$null | ForEach-Object {
+        $value, $description = $_ -split "`t", 2
+        if (-not $description) {
+            $description = $value
+        }
+        [System.Management.Automation.CompletionResult]::new($value, $value, 'ParameterValue', $description)
+    }
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The candidates for the last of `words`.
+    fn candidates(words: &[&str]) -> Vec<String> {
+        let words: Vec<String> = std::iter::once("ff").chain(words.iter().copied()).map(String::from).collect();
+        complete(&words, words.len() - 1)
+            .into_iter()
+            .map(|(candidate, _)| candidate)
+            .collect()
+    }
+
+    #[test]
+    fn flags() {
+        assert_eq!(candidates(&["--wi"]), ["--width"]);
+        assert!(candidates(&["-"]).contains(&"--format".to_string()));
+    }
+
+    #[test]
+    fn flag_values() {
+        assert_eq!(candidates(&["--align", "c"]), ["center"]);
+        assert_eq!(candidates(&["-a", ""]), ["left", "right", "center"]);
+        assert_eq!(candidates(&["--format=pro"]), ["--format=prometheus"]);
+        assert_eq!(candidates(&["--default-config=y"]), ["--default-config=yaml"]);
+        // `-w0.5` has its value, so an operand comes next
+        assert!(candidates(&["-w0.5", "con"]).contains(&"config".to_string()));
+    }
+
+    #[test]
+    fn lists_leave_out_given_items() {
+        let modules = candidates(&["--modules", "CPU,"]);
+        assert!(modules.contains(&"CPU,Memory".to_string()));
+        assert!(!modules.contains(&"CPU,CPU".to_string()));
+        assert_eq!(candidates(&["--stats", "CPU=name,us"]), ["CPU=name,usage"]);
+        assert!(candidates(&["-s", ""]).contains(&"Disks=".to_string()));
+    }
+
+    #[test]
+    fn commands_and_operands() {
+        assert_eq!(candidates(&["config", "s"]), ["set"]);
+        assert_eq!(candidates(&["list", ""]), ["modules", "stats"]);
+        assert_eq!(candidates(&["list", "stats", "Dis"]), ["Disks"]);
+        assert_eq!(candidates(&["completions", "z"]), ["zsh"]);
+        assert!(candidates(&["config", "get", "theme.w"]).contains(&"theme.width".to_string()));
+    }
+
+    #[test]
+    fn scripts_call_the_installed_binary() {
+        let call = format!("{} __complete", env!("CARGO_BIN_NAME"));
+        for shell in SHELLS {
+            let script = Shell::parse(shell).unwrap().script();
+            assert!(script.contains(&call), "{}", shell);
+        }
+    }
+}
@@ -0,0 +1,568 @@
+//! Command line parsing.
+//!
+//! Flags are described by [`FLAGS`] and commands by [`COMMANDS`]; both
+//! tables also drive the help output. Flags that take a value accept it as
+//! `--width 0.5`, `--width=0.5`, `-w 0.5`, `-w0.5` or `-w=0.5`, and short
+//! flags without a value can be combined (`-hv`). Everything after `--` is
+//! an operand, even if it starts with a dash.
+use crate::config::{Align, FileFormat};
+use crate::output::{statsd, OutputFormat};
+use crate::error::Error;
+use crate::suggest::{closest, did_you_mean};
+use std::env;
+use std::net::ToSocketAddrs;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Args {
+    Help,
+    Version,
+    Config,
+    Width,
+    Align,
+    Modules,
+    Profile,
+    Format,
+    Listen,
+    Cache,
+    Push,
+    /// `MODULE=stat,stat` to override the stats of a module, or the plain
+    /// list of stats for `ff config add-module`.
+    Stats,
+    ConfigFile,
+    ShowOrigin,
+    DefaultConfig,
+    Check,
+    Get,
+    Set,
+    Unset,
+    AddModule,
+    Completions,
+    Manpage,
+    ListModules,
+    ListStats,
+    Serve,
+    /// The value given to `ff config set`.
+    Value,
+}
+
+pub struct Flag {
+    pub short: Option<char>,
+    pub long: &'static str,
+    /// Name of the value the flag takes, if it takes one.
+    pub value: Option<&'static str>,
+    /// Value used when an optional value is left out. Optional values can
+    /// only be given with `=`.
+    pub default: Option<&'static str>,
+    pub arg: Args,
+    pub description: &'static str,
+    pub example: &'static str,
+}
+
+pub struct Command {
+    /// The words naming the command, e.g. `["config", "set"]`.
+    pub words: &'static [&'static str],
+    /// Names of the operands that follow the command.
+    pub operands: &'static [&'static str],
+    pub arg: Args,
+    pub description: &'static str,
+    pub example: &'static str,
+}
+
+/// Every flag, in the order it is documented.
+pub const FLAGS: &[Flag] = &[
+    Flag {
+        short: Some('w'),
+        long: "width",
+        value: Some("FRACTION"),
+        default: None,
+        arg: Args::Width,
+        description: "Change the width of the output in the terminal.\nThe Format is a float:\n0.0 - 1.0 (0% - 100%)",
+        example: "`-w 0.5` or `--width=0.5`",
+    },
+    Flag {
+        short: Some('a'),
+        long: "align",
+        value: Some("ALIGN"),
+        default: None,
+        arg: Args::Align,
+        description: "Change the alignment of the output in the terminal.\nThe Format is a string:\nleft, right, center",
+        example: "`-a left` or `--align=left`",
+    },
+    Flag {
+        short: Some('m'),
+        long: "modules",
+        value: Some("MODULES"),
+        default: None,
+        arg: Args::Modules,
+        description: "Show only these modules, in this order, for this run.\nModules that are not in the configuration are shown with all of their stats.",
+        example: "`--modules CPU,Memory`",
+    },
+    Flag {
+        short: Some('s'),
+        long: "stats",
+        value: Some("MODULE=STATS"),
+        default: None,
+        arg: Args::Stats,
+        description: "Show only these stats of a module for this run.\nMay be given once per module.",
+        example: "`--stats CPU=name,usage --stats Memory=used`",
+    },
+    Flag {
+        short: Some('p'),
+        long: "profile",
+        value: Some("NAME"),
+        default: None,
+        arg: Args::Profile,
+        description: "Use a profile from the configuration for this run.\nThe FF_PROFILE environment variable does the same.",
+        example: "`--profile banner`",
+    },
+    Flag {
+        short: Some('f'),
+        long: "format",
+        value: Some("FORMAT"),
+        default: None,
+        arg: Args::Format,
+        description: "Print the stats in a machine readable format instead of the terminal layout.\nThe Format is a string:\ntext, json, yaml, toml, csv, prometheus, influx",
+        example: "`--format json`",
+    },
+    Flag {
+        short: None,
+        long: "push",
+        value: Some("URL"),
+        default: None,
+        arg: Args::Push,
+        description: "Send the numeric stats to a StatsD server as gauges instead of printing them.\nThe Format is a URL:\nstatsd://HOST[:PORT], the port defaults to 8125",
+        example: "`--push statsd://127.0.0.1:8125`",
+    },
+    Flag {
+        short: Some('l'),
+        long: "listen",
+        value: Some("ADDRESS"),
+        default: None,
+        arg: Args::Listen,
+        description: "Address and port `ff serve` listens on.\nDefaults to 127.0.0.1:9108, use 0.0.0.0:9108 to accept other machines.",
+        example: "`--listen 0.0.0.0:9108`",
+    },
+    Flag {
+        short: None,
+        long: "cache",
+        value: Some("MILLISECONDS"),
+        default: None,
+        arg: Args::Cache,
+        description: "How long `ff serve` answers scrapes with the stats it collected last.\nDefaults to 1000.",
+        example: "`--cache 5000`",
+    },
+    Flag {
+        short: Some('c'),
+        long: "config",
+        value: None,
+        default: None,
+        arg: Args::Config,
+        description: "Change the configuration of the program.\nThis opens the configuration file in $VISUAL or $EDITOR (or the default application)\nand checks it when the editor exits, offering to edit it again or to restore the\nlast valid version if it has problems.",
+        example: "`-c` or `--config`",
+    },
+    Flag {
+        short: None,
+        long: "config-file",
+        value: Some("PATH"),
+        default: None,
+        arg: Args::ConfigFile,
+        description: "Read the user configuration from this file instead of the default location.\nThe FF_CONFIG environment variable does the same.",
+        example: "`--config-file ~/dotfiles/ff.json`",
+    },
+    Flag {
+        short: None,
+        long: "default-config",
+        value: Some("FORMAT"),
+        default: Some("json"),
+        arg: Args::DefaultConfig,
+        description: "Print the default configuration with explanatory comments.\nThe Format is a string:\njson, jsonc, toml, yaml",
+        example: "`--default-config=toml > ~/.config/ninju_fastfestch/config.toml`",
+    },
+    Flag {
+        short: None,
+        long: "show-origin",
+        value: None,
+        default: None,
+        arg: Args::ShowOrigin,
+        description: "Print every effective configuration value together with the file that set it.",
+        example: "`--show-origin`",
+    },
+    Flag {
+        short: Some('h'),
+        long: "help",
+        value: None,
+        default: None,
+        arg: Args::Help,
+        description: "Print this help.",
+        example: "`-h` or `--help`",
+    },
+    Flag {
+        short: Some('v'),
+        long: "version",
+        value: None,
+        default: None,
+        arg: Args::Version,
+        description: "Print the version of the program to the terminal.",
+        example: "`-v` or `--version`",
+    },
+];
+
+/// Every command, in the order it is documented.
+pub const COMMANDS: &[Command] = &[
+    Command {
+        words: &["config"],
+        operands: &[],
+        arg: Args::Config,
+        description: "Same as --config.",
+        example: "`ff config`",
+    },
+    Command {
+        words: &["config", "check"],
+        operands: &[],
+        arg: Args::Check,
+        description: "Check every config file for mistakes without printing any stats.\nEach problem is reported with its file, line and column.\nExits with code 4 if there are any.",
+        example: "`ff config check`",
+    },
+    Command {
+        words: &["config", "get"],
+        operands: &["KEY"],
+        arg: Args::Get,
+        description: "Print the effective value of a configuration key.\nKeys are written like theme.width or modules[0].stats.",
+        example: "`ff config get theme.width`",
+    },
+    Command {
+        words: &["config", "set"],
+        operands: &["KEY", "VALUE"],
+        arg: Args::Set,
+        description: "Change a value in the user configuration file.\nValues are read as JSON when possible and as text otherwise.\nThe file keeps its formatting and is checked before it is written.",
+        example: "`ff config set theme.align center`",
+    },
+    Command {
+        words: &["config", "unset"],
+        operands: &["KEY"],
+        arg: Args::Unset,
+        description: "Remove a value from the user configuration file.",
+        example: "`ff config unset theme.width`",
+    },
+    Command {
+        words: &["config", "add-module"],
+        operands: &["NAME"],
+        arg: Args::AddModule,
+        description: "Add a module to the end of the module list in the user configuration file.\nIt shows all of its stats unless --stats lists them.",
+        example: "`ff config add-module Disks --stats name,total,used`",
+    },
+    Command {
+        words: &["list", "modules"],
+        operands: &[],
+        arg: Args::ListModules,
+        description: "List the built-in modules and whether this machine can show them.",
+        example: "`ff list modules`",
+    },
+    Command {
+        words: &["list", "stats"],
+        operands: &["NAME"],
+        arg: Args::ListStats,
+        description: "List the stats of a module with their unit and whether this machine reports them.",
+        example: "`ff list stats Disks`",
+    },
+    Command {
+        words: &["serve"],
+        operands: &[],
+        arg: Args::Serve,
+        description: "Serve the stats over HTTP until stopped with SIGTERM or Ctrl-C:\n/metrics for Prometheus, /json like --format json and /healthz.\nThe modules are collected on each scrape, at most once per --cache interval.",
+        example: "`ff serve --listen 127.0.0.1:9108`",
+    },
+    Command {
+        words: &["completions"],
+        operands: &["SHELL"],
+        arg: Args::Completions,
+        description: "Print the completion script for a shell.\nThe Format is a string:\nbash, zsh, fish, elvish, powershell",
+        example: "`ff completions bash > ~/.local/share/bash-completion/completions/ff`",
+    },
+    Command {
+        words: &["manpage"],
+        operands: &[],
+        arg: Args::Manpage,
+        description: "Print the manual page, which has this help together with every module and stat.\nRead it with `ff manpage | man -l -`.",
+        example: "`ff manpage > ~/.local/share/man/man1/ff.1`",
+    },
+];
+
+impl Flag {
+    /// `-w, --width <FRACTION>`
+    pub fn usage(&self) -> String {
+        let mut usage = match self.short {
+            Some(short) => format!("-{}, --{}", short, self.long),
+            None => format!("--{}", self.long),
+        };
+        match (self.value, self.default) {
+            (Some(value), Some(_)) => usage.push_str(&format!("[={}]", value)),
+            (Some(value), None) => usage.push_str(&format!(" <{}>", value)),
+            (None, _) => {}
+        }
+        usage
+    }
+}
+
+impl Command {
+    /// `config set <KEY> <VALUE>`
+    pub fn usage(&self) -> String {
+        let mut usage = self.words.join(" ");
+        for operand in self.operands {
+            usage.push_str(&format!(" <{}>", operand));
+        }
+        usage
+    }
+}
+
+pub struct ArgHandler {
+    pub args: Vec<String>,
+}
+
+impl ArgHandler {
+    pub fn new() -> ArgHandler {
+        ArgHandler {
+            args: env::args().collect(),
+        }
+    }
+    /// `ff __complete N -- WORDS`, which the completion scripts run (see
+    /// [`crate::completions`]): the index of the word to complete and the
+    /// command line up to the cursor.
+    pub fn completion_request(&self) -> Option<(usize, &[String])> {
+        match self.args.get(1..4)? {
+            [command, index, separator] if command == "__complete" && separator == "--" => {
+                Some((index.parse().ok()?, &self.args[4..]))
+            }
+            _ => None,
+        }
+    }
+    /// Parses the arguments into `(argument, value)` pairs. The command,
+    /// if there is one, comes first, followed by the flags in the order
+    /// they were given. Flags without a value have an empty one.
+    pub fn handle(&self) -> Result<Vec<(Args, String)>, Error> {
+        let mut args = Vec::new();
+        let mut operands = Vec::new();
+        let mut words = self.args.iter().skip(1);
+        while let Some(word) = words.next() {
+            if word == "--" {
+                operands.extend(words.by_ref().cloned());
+            } else if let Some(long) = word.strip_prefix("--") {
+                let (name, value) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (long, None),
+                };
+                let flag = FLAGS.iter().find(|flag| flag.long == name).ok_or_else(|| {
+                    let hint = closest(name, FLAGS.iter().map(|flag| flag.long))
+                        .map_or_else(String::new, |long| format!(", did you mean `--{}`?", long));
+                    Error::Usage(format!("unknown flag `--{}`{}", name, hint))
+                })?;
+                args.push((flag.arg, flag_value(flag, value, &mut words)?));
+            } else if let Some(shorts) = word.strip_prefix('-').filter(|shorts| !shorts.is_empty()) {
+                for (index, short) in shorts.char_indices() {
+                    let flag = FLAGS
+                        .iter()
+                        .find(|flag| flag.short == Some(short))
+                        .ok_or_else(|| Error::Usage(format!("unknown flag `-{}` in `{}`", short, word)))?;
+                    if flag.value.is_none() {
+                        args.push((flag.arg, String::new()));
+                        continue;
+                    }
+                    // the rest of the word is the value: `-w0.5` or `-w=0.5`
+                    let rest = &shorts[index + short.len_utf8()..];
+                    let value = (!rest.is_empty()).then(|| rest.strip_prefix('=').unwrap_or(rest).to_string());
+                    args.push((flag.arg, flag_value(flag, value, &mut words)?));
+                    break;
+                }
+            } else {
+                operands.push(word.clone());
+            }
+        }
+        if !operands.is_empty() {
+            let command = parse_command(&operands)?;
+            args.splice(0..0, command);
+        }
+        Ok(args)
+    }
+}
+
+/// The value of `flag`, taken from `value` (given with `=` or attached to a
+/// short flag) or from the next word.
+fn flag_value<'a>(flag: &Flag, value: Option<String>, words: &mut impl Iterator<Item = &'a String>) -> Result<String, Error> {
+    let value = match (flag.value, value, flag.default) {
+        (None, None, _) => return Ok(String::new()),
+        (None, Some(_), _) => return Err(Error::Usage(format!("`--{}` does not take a value", flag.long))),
+        (Some(_), Some(value), _) => value,
+        (Some(_), None, Some(default)) => default.to_string(),
+        (Some(name), None, None) => words
+            .next()
+            .cloned()
+            .ok_or_else(|| Error::Usage(format!("`--{}` needs a {} value, e.g. {}", flag.long, name, flag.example)))?,
+    };
+    check_value(flag.arg, &value)
+        .map_err(|expected| Error::Usage(format!("invalid value `{}` for `--{}`: expected {}", value, flag.long, expected)))?;
+    Ok(value)
+}
+
+/// Checks the values that can be checked without the config.
+fn check_value(arg: Args, value: &str) -> Result<(), &'static str> {
+    let valid = match arg {
+        Args::Width => value.parse::<f64>().is_ok_and(|width| width > 0.0 && width <= 1.0),
+        Args::Align => Align::parse(value).is_some(),
+        Args::DefaultConfig => FileFormat::parse(value).is_some(),
+        Args::Format => OutputFormat::parse(value).is_some(),
+        Args::Listen => value.to_socket_addrs().is_ok(),
+        Args::Cache => value.parse::<u64>().is_ok(),
+        Args::Push => statsd::address(value).is_some(),
+        Args::Modules => value.split(',').all(|name| !name.trim().is_empty()),
+        _ => true,
+    };
+    if valid {
+        return Ok(());
+    }
+    Err(match arg {
+        Args::Width => "a fraction of the terminal width between 0.0 (exclusive) and 1.0",
+        Args::Align => "left, right or center",
+        Args::DefaultConfig => "json, jsonc, toml or yaml",
+        Args::Format => "text, json, yaml, toml, csv, prometheus or influx",
+        Args::Listen => "an address and port, e.g. 127.0.0.1:9108",
+        Args::Cache => "a number of milliseconds",
+        Args::Push => "a URL like statsd://127.0.0.1:8125",
+        _ => "a comma separated list of module names",
+    })
+}
+
+/// Matches the operands against [`COMMANDS`], preferring the longest
+/// command, e.g. `config set` over `config`.
+fn parse_command(operands: &[String]) -> Result<Vec<(Args, String)>, Error> {
+    let command = COMMANDS
+        .iter()
+        .filter(|command| {
+            command.words.len() <= operands.len()
+                && command.words.iter().zip(operands).all(|(word, operand)| word == operand)
+        })
+        .max_by_key(|command| command.words.len());
+    let Some(command) = command else {
+        let first = operands[0].as_str();
+        let subcommands: Vec<&str> = COMMANDS
+            .iter()
+            .filter(|command| command.words[0] == first)
+            .filter_map(|command| command.words.get(1).copied())
+            .collect();
+        // `ff list` and `ff list modlues` only exist with a subcommand
+        if !subcommands.is_empty() {
+            return Err(Error::Usage(match operands.get(1) {
+                Some(second) => format!("unknown command `{} {}`{}", first, second, did_you_mean(second, subcommands)),
+                None => format!("`{}` needs one of {}", first, subcommands.join(", ")),
+            }));
+        }
+        return Err(Error::Usage(format!(
+            "unknown command `{}`{}",
+            first,
+            did_you_mean(first, COMMANDS.iter().map(|command| command.words[0]))
+        )));
+    };
+    let rest = &operands[command.words.len()..];
+    if rest.len() < command.operands.len() {
+        return Err(Error::Usage(format!(
+            "`{}` needs {}, e.g. {}",
+            command.words.join(" "),
+            command.operands.join(" and "),
+            command.example
+        )));
+    }
+    if let Some(extra) = rest.get(command.operands.len()) {
+        // `ff config chek` is more likely a misspelt command than a stray word
+        if command.words.len() == 1 && command.operands.is_empty() {
+            let subcommands = COMMANDS
+                .iter()
+                .filter(|other| other.words[0] == command.words[0])
+                .filter_map(|other| other.words.get(1).copied());
+            return Err(Error::Usage(format!(
+                "unknown command `{} {}`{}",
+                command.words[0],
+                extra,
+                did_you_mean(extra, subcommands)
+            )));
+        }
+        return Err(Error::Usage(format!("unexpected argument `{}`", extra)));
+    }
+    let mut args = vec![(command.arg, rest.first().cloned().unwrap_or_default())];
+    if let Some(value) = rest.get(1) {
+        args.push((Args::Value, value.clone()));
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(words: &[&str]) -> Result<Vec<(Args, String)>, String> {
+        let args = std::iter::once("ff").chain(words.iter().copied()).map(String::from).collect();
+        ArgHandler { args }.handle().map_err(|err| err.to_string())
+    }
+
+    fn width(value: &str) -> Vec<(Args, String)> {
+        vec![(Args::Width, value.to_string())]
+    }
+
+    #[test]
+    fn flag_values_in_every_form() {
+        for words in [&["--width", "0.5"][..], &["--width=0.5"], &["-w", "0.5"], &["-w0.5"], &["-w=0.5"]] {
+            assert_eq!(parse(words), Ok(width("0.5")), "{:?}", words);
+        }
+    }
+
+    #[test]
+    fn short_flags_combine() {
+        assert_eq!(parse(&["-hv"]), Ok(vec![(Args::Help, String::new()), (Args::Version, String::new())]));
+        assert_eq!(parse(&["-hw", "0.5"]), Ok(vec![(Args::Help, String::new()), (Args::Width, "0.5".to_string())]));
+    }
+
+    #[test]
+    fn optional_values_need_an_equals_sign() {
+        assert_eq!(parse(&["--default-config"]), Ok(vec![(Args::DefaultConfig, "json".to_string())]));
+        assert_eq!(parse(&["--default-config=toml"]), Ok(vec![(Args::DefaultConfig, "toml".to_string())]));
+    }
+
+    #[test]
+    fn values_are_checked() {
+        assert_eq!(
+            parse(&["--width", "2"]),
+            Err("invalid value `2` for `--width`: expected a fraction of the terminal width between 0.0 (exclusive) and 1.0".to_string())
+        );
+        assert!(parse(&["--format", "xml"]).is_err());
+        assert!(parse(&["--push", "http://localhost"]).is_err());
+        assert_eq!(parse(&["--push", "statsd://localhost"]), Ok(vec![(Args::Push, "statsd://localhost".to_string())]));
+        assert!(parse(&["--width"]).unwrap_err().starts_with("`--width` needs a FRACTION value"));
+        assert_eq!(parse(&["--help=yes"]), Err("`--help` does not take a value".to_string()));
+    }
+
+    #[test]
+    fn unknown_flags_suggest_a_flag() {
+        assert_eq!(parse(&["--widht"]), Err("unknown flag `--widht`, did you mean `--width`?".to_string()));
+        assert_eq!(parse(&["--xyz"]), Err("unknown flag `--xyz`".to_string()));
+        assert_eq!(parse(&["-x"]), Err("unknown flag `-x` in `-x`".to_string()));
+    }
+
+    #[test]
+    fn commands_and_operands() {
+        assert_eq!(
+            parse(&["config", "set", "theme.width", "0.5"]),
+            Ok(vec![(Args::Set, "theme.width".to_string()), (Args::Value, "0.5".to_string())])
+        );
+        assert_eq!(parse(&["list", "stats", "CPU"]), Ok(vec![(Args::ListStats, "CPU".to_string())]));
+        assert_eq!(parse(&["serve", "-l", "127.0.0.1:0"]), Ok(vec![(Args::Serve, String::new()), (Args::Listen, "127.0.0.1:0".to_string())]));
+        assert_eq!(
+            parse(&["config", "set", "--", "theme.width", "-1"]),
+            Ok(vec![(Args::Set, "theme.width".to_string()), (Args::Value, "-1".to_string())])
+        );
+    }
+
+    #[test]
+    fn unknown_commands_suggest_a_command() {
+        assert_eq!(parse(&["confg"]), Err("unknown command `confg`, did you mean `config`?".to_string()));
+        assert_eq!(parse(&["list"]), Err("`list` needs one of modules, stats".to_string()));
+        assert_eq!(parse(&["list", "modlues"]), Err("unknown command `list modlues`, did you mean `modules`?".to_string()));
+    }
+}
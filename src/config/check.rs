@@ -0,0 +1,376 @@
+//! `ff config check`: validates every config file without collecting or
+//! rendering anything, so that dotfiles can be checked in CI.
+//!
+//! Each file is checked on its own, which means a problem is reported in
+//! the file that contains it even when another layer would override the
+//! value. Files that pass are then merged like [`import_config`] does and
+//! the result is checked once more.
+//!
+//! [`import_config`]: super::import_config
+use crate::config::{
+    config_io, layers, locate, migrate, model, parse_config, Align, Color, Config, FileFormat, Icon, Layer,
+    Origin, Unavailable, UnknownStats,
+};
+use crate::error::Error;
+use crate::modules;
+use crate::suggest::did_you_mean;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+const CONFIG_KEYS: &[&str] = &["version", "theme", "timeout", "unknown_stats", "modules", "profiles"];
+const PROFILE_KEYS: &[&str] = &["extends", "theme", "timeout", "unknown_stats", "modules"];
+const THEME_KEYS: &[&str] = &["gradient", "text", "width", "align"];
+const GRADIENT_KEYS: &[&str] = &["from", "to"];
+const TEXT_KEYS: &[&str] = &["headers", "groups", "key", "value"];
+pub(super) const MODULE_KEYS: &[&str] = &["name", "icon", "stats", "plugin", "unavailable", "timeout"];
+
+/// A problem found in a config file.
+pub struct Problem {
+    pub file: PathBuf,
+    /// Line and column of the offending value, when it could be found.
+    pub location: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl Problem {
+    /// `file:line:column`, or just the file when there is no location.
+    pub fn position(&self) -> String {
+        match self.location {
+            Some((line, column)) => format!("{}:{}:{}", self.file.display(), line, column),
+            None => self.file.display().to_string(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Report {
+    /// Every file that was checked, in layer order.
+    pub files: Vec<PathBuf>,
+    pub problems: Vec<Problem>,
+    /// Remarks that do not make the config invalid.
+    pub notes: Vec<String>,
+}
+
+/// Checks the system, user and project files. Unlike loading the config,
+/// this never creates or upgrades a file.
+pub fn check(config_file: Option<&Path>) -> Result<Report, Error> {
+    let mut origins = Vec::new();
+    origins.extend(layers::system_config_path().map(Origin::System));
+    match layers::explicit_config_path(config_file) {
+        Some(path) if !path.exists() => {
+            return Err(Error::ConfigIo {
+                path,
+                source: io::Error::new(io::ErrorKind::NotFound, "config file does not exist"),
+            })
+        }
+        Some(path) => origins.push(Origin::User(path)),
+        None => origins.extend(layers::user_config_file()?.map(Origin::User)),
+    }
+    origins.extend(layers::project_config_path().map(Origin::Project));
+
+    let mut report = Report::default();
+    let mut sources = vec![Layer {
+        origin: Origin::Default,
+        value: serde_json::to_value(Config::default()).expect("the default config always serializes"),
+    }];
+    for origin in origins {
+        let path = origin.path().expect("config files have a path").to_path_buf();
+        if let Some(value) = check_file(&path, &mut report)? {
+            sources.push(Layer { origin, value });
+        }
+    }
+    if report.problems.is_empty() {
+        let (value, origins) = layers::merge(&sources);
+        if let Err(err) = parse_config(value) {
+            report.problems.push(Problem {
+                file: origins
+                    .get(&err.path)
+                    .and_then(Origin::path)
+                    .map_or_else(|| PathBuf::from("config"), Path::to_path_buf),
+                location: None,
+                message: err.to_string(),
+            });
+        }
+    }
+    Ok(report)
+}
+
+/// Checks a single file and adds what it finds to `report`. Returns the
+/// (upgraded) contents of the file if it has no problems.
+pub fn check_file(path: &Path, report: &mut Report) -> Result<Option<Value>, Error> {
+    let content = fs::read_to_string(path).map_err(config_io(path))?;
+    let format = FileFormat::from_path(path);
+    report.files.push(path.to_path_buf());
+    let mut value = match format.parse_located(&content) {
+        Ok(value) => value,
+        Err((message, location)) => {
+            report.problems.push(Problem {
+                file: path.to_path_buf(),
+                location,
+                message,
+            });
+            return Ok(None);
+        }
+    };
+    let mut problems = Vec::new();
+    match migrate::migrate(&mut value) {
+        Ok(changes) if !changes.is_empty() => report.notes.push(format!(
+            "{} uses an older config version and will be upgraded the next time ff runs",
+            path.display()
+        )),
+        Ok(_) => {}
+        Err(err) => problems.push((err.path, err.message)),
+    }
+    validate(&value, &mut problems);
+    if problems.is_empty() {
+        return Ok(Some(value));
+    }
+    let mut problems: Vec<Problem> = problems
+        .into_iter()
+        .map(|(at, message)| Problem {
+            file: path.to_path_buf(),
+            location: locate::locate(&content, format, &at).map(|offset| locate::line_column(&content, offset)),
+            message,
+        })
+        .collect();
+    problems.sort_by_key(|problem| problem.location);
+    report.problems.extend(problems);
+    Ok(None)
+}
+
+/// Problems as the path of the offending value and a message.
+pub(super) type Problems = Vec<(String, String)>;
+
+pub(super) fn validate(config: &Value, problems: &mut Problems) {
+    let Some(config) = table(config, "", CONFIG_KEYS, problems) else {
+        return;
+    };
+    if let Some(version) = config.get("version") {
+        typed::<String>(version, "version", problems);
+    }
+    if let Some(timeout) = config.get("timeout") {
+        typed::<u64>(timeout, "timeout", problems);
+    }
+    if let Some(unknown_stats) = config.get("unknown_stats") {
+        typed::<UnknownStats>(unknown_stats, "unknown_stats", problems);
+    }
+    if let Some(theme) = config.get("theme") {
+        validate_theme(theme, problems);
+    }
+    match config.get("modules") {
+        Some(Value::Array(modules)) => {
+            for (index, module) in modules.iter().enumerate() {
+                validate_module(module, &format!("modules[{}]", index), problems);
+            }
+        }
+        Some(_) => problems.push(("modules".to_string(), "`modules` must be a list".to_string())),
+        None => {}
+    }
+    match config.get("profiles") {
+        Some(Value::Object(profiles)) => {
+            for (name, profile) in profiles {
+                validate_profile(profile, name, profiles, problems);
+            }
+        }
+        Some(_) => problems.push(("profiles".to_string(), "`profiles` must be a table of profiles".to_string())),
+        None => {}
+    }
+}
+
+/// A profile holds the settings of the config itself, apart from `version`
+/// and other profiles, and may name the profile it `extends`.
+fn validate_profile(profile: &Value, name: &str, profiles: &Map<String, Value>, problems: &mut Problems) {
+    let path = layers::join("profiles", name);
+    let Some(settings) = table(profile, &path, PROFILE_KEYS, problems) else {
+        return;
+    };
+    let extends_path = layers::join(&path, "extends");
+    if let Some(extends) = settings.get("extends").and_then(|extends| typed::<String>(extends, &extends_path, problems)) {
+        if !profiles.contains_key(&extends) {
+            problems.push((
+                extends_path,
+                format!("unknown profile `{}`{}", extends, did_you_mean(&extends, profiles.keys().map(String::as_str))),
+            ));
+        } else {
+            // follow the chain until it ends, loops back here or loops elsewhere
+            let mut chain = vec![name];
+            let mut next = Some(extends.as_str());
+            while let Some(profile) = next.filter(|profile| !chain.contains(profile)) {
+                chain.push(profile);
+                next = profiles.get(profile).and_then(|profile| profile.get("extends")).and_then(Value::as_str);
+            }
+            if next == Some(name) {
+                let message = format!("profile `{}` extends itself: `{}` -> `{}`", name, chain.join("` -> `"), name);
+                problems.push((extends_path, message));
+            }
+        }
+    }
+    let mut settings = settings.clone();
+    settings.remove("extends");
+    let mut found = Vec::new();
+    validate(&Value::Object(settings), &mut found);
+    problems.extend(found.into_iter().map(|(at, message)| (layers::join(&path, &at), message)));
+}
+
+fn validate_theme(theme: &Value, problems: &mut Problems) {
+    let Some(theme) = table(theme, "theme", THEME_KEYS, problems) else {
+        return;
+    };
+    for (key, keys) in [("gradient", GRADIENT_KEYS), ("text", TEXT_KEYS)] {
+        let path = layers::join("theme", key);
+        let Some(colors) = theme.get(key).and_then(|colors| table(colors, &path, keys, problems)) else {
+            continue;
+        };
+        for (key, color) in colors {
+            typed::<Color>(color, &layers::join(&path, key), problems);
+        }
+    }
+    if let Some(width) = theme.get("width") {
+        if let Err(err) = model::deserialize_width(width.clone()) {
+            problems.push(("theme.width".to_string(), format!("`theme.width`: {}", err)));
+        }
+    }
+    if let Some(align) = theme.get("align") {
+        typed::<Align>(align, "theme.align", problems);
+    }
+}
+
+fn validate_module(module: &Value, path: &str, problems: &mut Problems) {
+    let Some(module) = table(module, path, MODULE_KEYS, problems) else {
+        return;
+    };
+    let plugin = module.get("plugin").and_then(|plugin| typed::<PathBuf>(plugin, &layers::join(path, "plugin"), problems));
+    if let Some(plugin) = &plugin {
+        // a bare name is looked up in $PATH when the plugin is started
+        if plugin.components().count() > 1 && !plugin.exists() {
+            problems.push((
+                layers::join(path, "plugin"),
+                format!("plugin `{}` does not exist", plugin.display()),
+            ));
+        }
+    }
+    let name = match module.get("name") {
+        Some(name) => typed::<String>(name, &layers::join(path, "name"), problems),
+        None => {
+            problems.push((path.to_string(), format!("`{}` has no `name`", path)));
+            None
+        }
+    };
+    let info = match (&name, &plugin) {
+        (Some(name), None) => {
+            let info = modules::find(name);
+            if info.is_none() {
+                problems.push((
+                    layers::join(path, "name"),
+                    format!(
+                        "unknown module `{}`{}",
+                        name,
+                        did_you_mean(name, modules::REGISTRY.iter().map(|info| info.name))
+                    ),
+                ));
+            }
+            info
+        }
+        _ => None,
+    };
+    if let Some(stats) = module.get("stats") {
+        let stats_path = layers::join(path, "stats");
+        if let (Some(stats), Some(info)) = (typed::<Vec<String>>(stats, &stats_path, problems), info) {
+            for (index, stat) in stats.iter().enumerate() {
+                if !info.has_stat(stat) {
+                    problems.push((
+                        format!("{}[{}]", stats_path, index),
+                        format!(
+                            "unknown stat `{}` for module `{}`{}",
+                            stat,
+                            info.name,
+                            did_you_mean(stat, info.stat_names())
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+    if let Some(icon) = module.get("icon") {
+        typed::<Icon>(icon, &layers::join(path, "icon"), problems);
+    }
+    if let Some(unavailable) = module.get("unavailable") {
+        typed::<Unavailable>(unavailable, &layers::join(path, "unavailable"), problems);
+    }
+    if let Some(timeout) = module.get("timeout") {
+        typed::<u64>(timeout, &layers::join(path, "timeout"), problems);
+    }
+}
+
+/// `value` as an object, reporting it if it is not one and reporting every
+/// key that is not in `keys`.
+fn table<'a>(value: &'a Value, path: &str, keys: &[&str], problems: &mut Problems) -> Option<&'a Map<String, Value>> {
+    let Value::Object(map) = value else {
+        let message = if path.is_empty() {
+            "the config must be a table of settings".to_string()
+        } else {
+            format!("`{}` must be a table of settings", path)
+        };
+        problems.push((path.to_string(), message));
+        return None;
+    };
+    for key in map.keys().filter(|key| !keys.contains(&key.as_str())) {
+        let message = if path.is_empty() {
+            format!("unknown key `{}`", key)
+        } else {
+            format!("unknown key `{}` in `{}`", key, path)
+        };
+        problems.push((layers::join(path, key), message + &did_you_mean(key, keys.iter().copied())));
+    }
+    Some(map)
+}
+
+/// `value` deserialized as the type the config expects at `path`.
+fn typed<T: DeserializeOwned>(value: &Value, path: &str, problems: &mut Problems) -> Option<T> {
+    serde_json::from_value(value.clone())
+        .map_err(|err| problems.push((path.to_string(), format!("`{}`: {}", path, err))))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The problems `check_file` finds in `content`, as `ff config check`
+    /// prints them.
+    fn problems(name: &str, content: &str) -> Vec<String> {
+        let path = std::env::temp_dir().join(format!("ff-check-{}-{}", std::process::id(), name));
+        fs::write(&path, content).unwrap();
+        let mut report = Report::default();
+        let checked = check_file(&path, &mut report);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(checked.unwrap().is_some(), report.problems.is_empty());
+        let prefix = format!("{}:", path.display());
+        report
+            .problems
+            .iter()
+            .map(|problem| format!("{}: {}", problem.position().replacen(&prefix, "", 1), problem.message))
+            .collect()
+    }
+
+    #[test]
+    fn syntax_errors_are_located() {
+        assert_eq!(problems("config.json", r#"{"theme": {"width": 0.5,}}"#), ["1:25: trailing comma"]);
+        assert_eq!(problems("config.toml", "[theme]\nwidth = = 2\n")[0].lines().next(), Some("2:9: invalid string"));
+        assert!(problems("config.yaml", "theme:\n  width: [1\n  x: 2\n")[0].starts_with("3:4: "));
+    }
+
+    #[test]
+    fn problems_are_located_in_every_format() {
+        let expected = ["4:7: unknown stat `usagee` for module `CPU`, did you mean `usage`?".to_string()];
+        let json = "{\n  \"modules\": [\n    { \"name\": \"CPU\",\n      \"stats\": [\"usagee\"] }\n  ]\n}\n";
+        let toml = "# mine\n[[modules]]\nname = \"CPU\"\nstats = [\"name\", \"usagee\"]\n";
+        let yaml = "modules:\n  - name: CPU\n    stats:\n      - usagee\n";
+        assert_eq!(problems("config.json", json), [expected[0].replace("4:7", "4:17")]);
+        assert_eq!(problems("config.toml", toml), [expected[0].replace("4:7", "4:18")]);
+        assert_eq!(problems("config.yaml", yaml), expected);
+        assert!(problems("config.jsonc", "// mine\n{ \"timeout\": 100, }\n").is_empty());
+    }
+}
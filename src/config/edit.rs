@@ -0,0 +1,621 @@
+//! `ff config get/set/unset/add-module`: reads and edits the user's config
+//! file from the command line.
+//!
+//! JSON and JSONC files are edited as text and TOML files with
+//! `toml_edit`, so comments, indentation and the order of keys and modules
+//! are kept and only the edited value changes. YAML files are written back
+//! from their parsed value, which keeps the order but not the comments.
+//!
+//! The edited file is checked like `ff config check` does before it is
+//! written, and an edit that would introduce a problem is refused.
+use crate::config::formats::{self, strip_jsonc, FileFormat};
+use crate::config::layers::{join, segments, Segment};
+use crate::config::locate::{json_member, skip_whitespace};
+use crate::config::{check, config_io};
+use crate::error::Error;
+use serde_json::{Map, Value};
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+pub enum Edit {
+    /// Sets the value at a path, creating any missing parent tables.
+    Set(String, Value),
+    Unset(String),
+    /// Appends an item to the list at a path.
+    Append(String, Value),
+}
+
+impl Edit {
+    pub fn path(&self) -> &str {
+        match self {
+            Edit::Set(path, _) | Edit::Unset(path) | Edit::Append(path, _) => path,
+        }
+    }
+}
+
+/// Reads a value given on the command line: JSON if it parses as JSON
+/// (`0.5`, `true`, `["name", "total"]`), a plain string otherwise.
+pub fn parse_value(text: &str) -> Value {
+    serde_json::from_str(text).unwrap_or_else(|_| Value::from(text))
+}
+
+/// The value at `path`, written like `theme.width` or `modules[0].stats`.
+pub fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    segments(path).into_iter().try_fold(value, |value, segment| match segment {
+        Segment::Key(key) => value.get(key),
+        Segment::Index(index) => value.get(index),
+    })
+}
+
+/// Applies `edit` to the config file at `path`. `effective` is the merged
+/// config; appending to a list the file does not set yet starts from the
+/// list currently in effect, so that adding a module keeps the others.
+pub fn edit_file(path: &Path, edit: Edit, effective: &Value) -> Result<(), Error> {
+    let content = fs::read_to_string(path).map_err(config_io(path))?;
+    let format = FileFormat::from_path(path);
+    let changed = edit.path().to_string();
+    let invalid = |message: String| {
+        let prefix = format!("`{}`: ", changed);
+        let message = message.strip_prefix(&prefix).unwrap_or(&message);
+        Error::Usage(format!("cannot change `{}`: {}", changed, message))
+    };
+    let before = format.parse_str(&content).map_err(|err| invalid(err.message))?;
+    let edit = match edit {
+        Edit::Append(list, item) if lookup(&before, &list).is_none() => {
+            let mut items = lookup(effective, &list)
+                .and_then(Value::as_array)
+                .cloned()
+                .unwrap_or_default();
+            items.push(item);
+            Edit::Set(list, Value::Array(items))
+        }
+        edit => edit,
+    };
+    let edited = match format {
+        FileFormat::Json | FileFormat::Jsonc => edit_json(&content, &edit),
+        FileFormat::Toml => edit_toml(&content, &edit),
+        FileFormat::Yaml => {
+            let mut value = before.clone();
+            apply(&mut value, &edit).map(|_| formats::render(&value, FileFormat::Yaml))
+        }
+    }
+    .map_err(invalid)?;
+    let after = format
+        .parse_str(&edited)
+        .map_err(|err| invalid(format!("the edit produced an unreadable file: {}", err.message)))?;
+    // only refuse problems that the edit introduced
+    let mut known = Vec::new();
+    check::validate(&before, &mut known);
+    let mut problems = Vec::new();
+    check::validate(&after, &mut problems);
+    if let Some((_, message)) = problems.into_iter().find(|(_, message)| !known.iter().any(|(_, known)| known == message)) {
+        return Err(invalid(message));
+    }
+    fs::write(path, edited).map_err(config_io(path))
+}
+
+/// `content` changed from `before` to `after` with the edits `ff config
+/// set` and `unset` make, so that comments and layout are kept. `None` for
+/// YAML files, whose comments would be lost, and if the edits do not give
+/// `after`.
+pub fn rewrite(content: &str, format: FileFormat, before: &Value, after: &Value) -> Option<String> {
+    let mut edits = Vec::new();
+    diff(before, after, "", &mut edits);
+    let mut content = content.to_string();
+    for edit in &edits {
+        content = match format {
+            FileFormat::Json | FileFormat::Jsonc => edit_json(&content, edit),
+            FileFormat::Toml => edit_toml(&content, edit),
+            FileFormat::Yaml => return None,
+        }
+        .ok()?;
+    }
+    (format.parse_str(&content).ok()? == *after).then_some(content)
+}
+
+/// The edits that turn `before` into `after`. Lists of the same length are
+/// edited item by item, so that changing one module leaves the others.
+fn diff(before: &Value, after: &Value, path: &str, edits: &mut Vec<Edit>) {
+    match (before, after) {
+        (Value::Object(old), Value::Object(new)) => {
+            for (key, value) in new {
+                match old.get(key) {
+                    Some(previous) => diff(previous, value, &join(path, key), edits),
+                    None => edits.push(Edit::Set(join(path, key), value.clone())),
+                }
+            }
+            for key in old.keys().filter(|key| !new.contains_key(*key)) {
+                edits.push(Edit::Unset(join(path, key)));
+            }
+        }
+        (Value::Array(old), Value::Array(new)) if old.len() == new.len() && !path.is_empty() => {
+            for (index, (previous, value)) in old.iter().zip(new).enumerate() {
+                diff(previous, value, &format!("{}[{}]", path, index), edits);
+            }
+        }
+        (previous, value) if previous != value => edits.push(Edit::Set(path.to_string(), value.clone())),
+        _ => {}
+    }
+}
+
+/// Splits `path` into the path of its parent and its last step.
+fn split_last(path: &str) -> Option<(&str, Segment<'_>)> {
+    let last = segments(path).pop()?;
+    let parent = match last {
+        Segment::Key(key) => path[..path.len() - key.len()].trim_end_matches('.'),
+        Segment::Index(_) => &path[..path.rfind('[')?],
+    };
+    Some((parent, last))
+}
+
+fn not_set(path: &str) -> String {
+    format!("`{}` is not set in the file", path)
+}
+
+/// Applies `edit` to a parsed config.
+fn apply(value: &mut Value, edit: &Edit) -> Result<(), String> {
+    let (parent_path, last) = split_last(edit.path()).ok_or("no key given")?;
+    if let Edit::Append(path, item) = edit {
+        return match lookup_mut(value, path, false)? {
+            Value::Array(items) => {
+                items.push(item.clone());
+                Ok(())
+            }
+            _ => Err(format!("`{}` is not a list", path)),
+        };
+    }
+    let parent = lookup_mut(value, parent_path, matches!(edit, Edit::Set(..)))?;
+    match (edit, last, parent) {
+        (Edit::Set(_, new), Segment::Key(key), Value::Object(map)) => {
+            map.insert(key.to_string(), new.clone());
+        }
+        (Edit::Set(_, new), Segment::Index(index), Value::Array(items)) if index < items.len() => {
+            items[index] = new.clone();
+        }
+        (Edit::Unset(path), Segment::Key(key), Value::Object(map)) => {
+            map.shift_remove(key).ok_or_else(|| not_set(path))?;
+        }
+        (Edit::Unset(_), Segment::Index(index), Value::Array(items)) if index < items.len() => {
+            items.remove(index);
+        }
+        (_, Segment::Index(index), Value::Array(_)) => return Err(format!("`{}` has no item {}", parent_path, index)),
+        _ => return Err(format!("`{}` is not a {}", parent_path, kind(last))),
+    }
+    Ok(())
+}
+
+fn kind(segment: Segment) -> &'static str {
+    match segment {
+        Segment::Key(_) => "table",
+        Segment::Index(_) => "list",
+    }
+}
+
+fn lookup_mut<'a>(mut value: &'a mut Value, path: &str, create: bool) -> Result<&'a mut Value, String> {
+    for segment in segments(path) {
+        value = match (segment, value) {
+            (Segment::Key(key), Value::Object(map)) => match create {
+                true => map.entry(key.to_string()).or_insert_with(|| Value::Object(Map::new())),
+                false => map.get_mut(key).ok_or_else(|| not_set(path))?,
+            },
+            (Segment::Index(index), Value::Array(items)) => items.get_mut(index).ok_or_else(|| not_set(path))?,
+            (segment, _) => return Err(format!("`{}` is not a {}", path, kind(segment))),
+        };
+    }
+    Ok(value)
+}
+
+fn edit_json(content: &str, edit: &Edit) -> Result<String, String> {
+    let stripped = strip_jsonc(content);
+    match edit {
+        Edit::Set(path, new) => set_json(content, &stripped, path, new),
+        Edit::Unset(path) => {
+            let (start, value) = json_member(&stripped, path).ok_or_else(|| not_set(path))?;
+            if path.is_empty() {
+                return Err("no key given".to_string());
+            }
+            let bytes = stripped.as_bytes();
+            let after = skip_whitespace(bytes, value.end);
+            if bytes.get(after) == Some(&b',') {
+                // remove the member and the separator up to the next member
+                let removed = member_lines(content, &stripped, start, after + 1)
+                    .unwrap_or_else(|| start..skip_whitespace(bytes, after + 1));
+                return Ok(format!("{}{}", &content[..removed.start], &content[removed.end..]));
+            }
+            // the last member: remove the separator before it instead
+            let before = stripped[..start].trim_end().len();
+            let comma = stripped[..before].ends_with(',').then(|| before - 1);
+            match (member_lines(content, &stripped, start, value.end), comma) {
+                (Some(lines), Some(comma)) => Ok(format!(
+                    "{}{}{}",
+                    &content[..comma],
+                    &content[comma + 1..lines.start],
+                    &content[lines.end..]
+                )),
+                (Some(lines), None) => Ok(format!("{}{}", &content[..lines.start], &content[lines.end..])),
+                (None, Some(comma)) => Ok(format!("{}{}", &content[..comma], &content[value.end..])),
+                (None, None) => Ok(format!("{}{}", &content[..before], &content[after..])),
+            }
+        }
+        Edit::Append(path, item) => {
+            let (_, list) = json_member(&stripped, path).ok_or_else(|| not_set(path))?;
+            if stripped.as_bytes()[list.start] != b'[' {
+                return Err(format!("`{}` is not a list", path));
+            }
+            Ok(insert_json(content, &stripped, list, None, item))
+        }
+    }
+}
+
+/// The whole lines of the member from `start` to `end`, if it has them to
+/// itself, together with the comment lines right above it: from the start
+/// of the first of them to after the newline that ends the member's line,
+/// so that a comment after the member goes with it.
+fn member_lines(content: &str, stripped: &str, start: usize, end: usize) -> Option<Range<usize>> {
+    let line_start = stripped[..start].rfind('\n').map_or(0, |newline| newline + 1);
+    let line_end = stripped[end..].find('\n').map_or(stripped.len(), |newline| end + newline + 1);
+    if !stripped[line_start..start].trim().is_empty() || !stripped[end..line_end].trim().is_empty() {
+        return None;
+    }
+    let mut first = line_start;
+    while first > 0 {
+        let previous = stripped[..first - 1].rfind('\n').map_or(0, |newline| newline + 1);
+        // comments are blanked out in `stripped`, but not in `content`
+        if !stripped[previous..first].trim().is_empty() || content[previous..first].trim().is_empty() {
+            break;
+        }
+        first = previous;
+    }
+    Some(first..line_end)
+}
+
+fn set_json(content: &str, stripped: &str, path: &str, new: &Value) -> Result<String, String> {
+    if let Some((start, value)) = json_member(stripped, path) {
+        let text = json_text(new, &line_indent(content, start), &indent_unit(content));
+        return Ok(format!("{}{}{}", &content[..value.start], text, &content[value.end..]));
+    }
+    let (parent, last) = split_last(path).ok_or("no key given")?;
+    match (last, json_member(stripped, parent)) {
+        (Segment::Key(key), Some((_, object))) if stripped.as_bytes()[object.start] == b'{' => {
+            Ok(insert_json(content, stripped, object, Some(key), new))
+        }
+        (Segment::Key(key), None) => {
+            // create the missing parent with the new key inside it
+            let mut object = Map::new();
+            object.insert(key.to_string(), new.clone());
+            set_json(content, stripped, parent, &Value::Object(object))
+        }
+        (Segment::Index(index), Some((_, list))) if stripped.as_bytes()[list.start] == b'[' => {
+            Err(format!("`{}` has no item {}", parent, index))
+        }
+        (last, _) => Err(format!("`{}` is not a {}", parent, kind(last))),
+    }
+}
+
+/// Inserts a member (with `key` in objects) at the end of the object or
+/// list spanning `range`, laid out like the members already there.
+fn insert_json(content: &str, stripped: &str, range: Range<usize>, key: Option<&str>, new: &Value) -> String {
+    let bytes = stripped.as_bytes();
+    let open = range.start;
+    let close = range.end - 1;
+    let first = skip_whitespace(bytes, open + 1);
+    let unit = indent_unit(content);
+    let mut end = stripped[..close].trim_end().len();
+    // JSONC allows a comma after the last member, which `stripped` blanks
+    // out; it then separates the new member, which gets one of its own
+    let trailing = match content[end..close].trim_start().strip_prefix(',') {
+        Some(rest) => {
+            end = close - rest.len();
+            ","
+        }
+        None => "",
+    };
+    let (indent, separator) = if first == close {
+        (line_indent(content, open) + &unit, "")
+    } else if !trailing.is_empty() {
+        (line_indent(content, first), "")
+    } else {
+        (line_indent(content, first), ",")
+    };
+    if first != close && !stripped[open..first].contains('\n') {
+        // members written on one line, e.g. `["name", "total"]`, stay on it
+        let value = serde_json::to_string(new).expect("config values always serialize");
+        let member = match key {
+            Some(key) => format!("{}: {}", Value::from(key), value),
+            None => value,
+        };
+        return format!("{}{} {}{}{}", &content[..end], separator, member, trailing, &content[end..]);
+    }
+    let member = match key {
+        Some(key) => format!("{}: {}{}", Value::from(key), json_text(new, &indent, &unit), trailing),
+        None => json_text(new, &indent, &unit) + trailing,
+    };
+    if first == close {
+        let outer = line_indent(content, open);
+        return format!("{}\n{}{}\n{}{}", &content[..open + 1], indent, member, outer, &content[close..]);
+    }
+    // after a comment that ends the line of the last member
+    let line_end = stripped[end..close].find('\n').map_or(end, |newline| end + newline);
+    format!(
+        "{}{}{}\n{}{}{}",
+        &content[..end],
+        separator,
+        &content[end..line_end],
+        indent,
+        member,
+        &content[line_end..]
+    )
+}
+
+/// `value` as JSON text starting at a line indented by `indent`.
+fn json_text(value: &Value, indent: &str, unit: &str) -> String {
+    if formats::is_inline(value) {
+        return formats::scalar(value);
+    }
+    let mut out = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(unit.as_bytes());
+    let mut serializer = serde_json::Serializer::with_formatter(&mut out, formatter);
+    serde::Serialize::serialize(value, &mut serializer).expect("config values always serialize");
+    String::from_utf8(out)
+        .expect("serde_json writes UTF-8")
+        .replace('\n', &format!("\n{}", indent))
+}
+
+fn line_indent(content: &str, position: usize) -> String {
+    let line = &content[content[..position].rfind('\n').map_or(0, |newline| newline + 1)..];
+    line[..line.len() - line.trim_start_matches([' ', '\t']).len()].to_string()
+}
+
+/// The indentation of the first indented line, two spaces if there is none.
+fn indent_unit(content: &str) -> String {
+    content
+        .lines()
+        .map(|line| &line[..line.len() - line.trim_start_matches([' ', '\t']).len()])
+        .find(|indent| !indent.is_empty())
+        .unwrap_or("  ")
+        .to_string()
+}
+
+fn edit_toml(content: &str, edit: &Edit) -> Result<String, String> {
+    use toml_edit::{DocumentMut, Item};
+    let mut document: DocumentMut = content.parse().map_err(|err: toml_edit::TomlError| err.to_string())?;
+    let (parent_path, last) = split_last(edit.path()).ok_or("no key given")?;
+    let create = !matches!(edit, Edit::Unset(_));
+    let (parent_path, last) = match edit {
+        Edit::Append(path, _) => (path.as_str(), None),
+        _ => (parent_path, Some(last)),
+    };
+    let mut item = document.as_item_mut();
+    for segment in segments(parent_path) {
+        let next = match segment {
+            Segment::Key(key) if item.is_table_like() => item.get_mut(key),
+            Segment::Index(index) => item.get_mut(index),
+            segment => return Err(format!("`{}` is not a {}", parent_path, kind(segment))),
+        };
+        item = next.ok_or_else(|| not_set(parent_path))?;
+        if item.is_none() {
+            if !create {
+                return Err(not_set(parent_path));
+            }
+            let mut table = toml_edit::Table::new();
+            table.set_implicit(true);
+            *item = Item::Table(table);
+        }
+    }
+    match (edit, last) {
+        (Edit::Append(path, new), _) => match item {
+            Item::ArrayOfTables(tables) => match toml_item(new, true)? {
+                Item::Table(table) => tables.push(table),
+                _ => return Err(format!("`{}` is a list of tables", path)),
+            },
+            Item::Value(toml_edit::Value::Array(items)) => items.push(toml_value(new)?),
+            _ => return Err(format!("`{}` is not a list", path)),
+        },
+        (Edit::Set(_, new), Some(Segment::Key(key))) => {
+            let in_table = item.is_table();
+            let table = item
+                .as_table_like_mut()
+                .ok_or_else(|| format!("`{}` is not a table", parent_path))?;
+            let new = toml_item(new, in_table)?;
+            match table.get_mut(key) {
+                // keep the key where it is, with its comments
+                Some(Item::Value(value)) if new.is_value() => {
+                    let decor = value.decor().clone();
+                    *value = new.into_value().expect("checked above");
+                    *value.decor_mut() = decor;
+                }
+                _ => {
+                    table.insert(key, new);
+                }
+            }
+        }
+        (Edit::Set(_, new), Some(Segment::Index(index))) => {
+            let in_table = matches!(item, Item::ArrayOfTables(_));
+            let slot = item
+                .get_mut(index)
+                .ok_or_else(|| format!("`{}` has no item {}", parent_path, index))?;
+            *slot = toml_item(new, in_table)?;
+        }
+        (Edit::Unset(path), Some(Segment::Key(key))) => {
+            item.as_table_like_mut()
+                .and_then(|table| table.remove(key))
+                .ok_or_else(|| not_set(path))?;
+        }
+        (Edit::Unset(_), Some(Segment::Index(index))) => match item {
+            Item::ArrayOfTables(tables) if index < tables.len() => tables.remove(index),
+            Item::Value(toml_edit::Value::Array(items)) if index < items.len() => {
+                items.remove(index);
+            }
+            _ => return Err(format!("`{}` has no item {}", parent_path, index)),
+        },
+        (_, None) => unreachable!("only appending has no last step"),
+    }
+    // a list of tables that has been emptied cannot be written
+    if let Some(Item::ArrayOfTables(tables)) = document.get("modules") {
+        if tables.is_empty() {
+            document.insert("modules", Item::Value(toml_edit::Value::Array(Default::default())));
+        }
+    }
+    Ok(document.to_string())
+}
+
+/// `value` as a TOML item: tables and lists of tables when it is placed in
+/// a table, inline values otherwise.
+fn toml_item(value: &Value, in_table: bool) -> Result<toml_edit::Item, String> {
+    use toml_edit::{ArrayOfTables, Item, Table};
+    match value {
+        Value::Object(map) if in_table => {
+            let mut table = Table::new();
+            for (key, value) in map {
+                table.insert(key, toml_item(value, true)?);
+            }
+            Ok(Item::Table(table))
+        }
+        Value::Array(items) if in_table && !items.is_empty() && items.iter().all(Value::is_object) => {
+            let mut tables = ArrayOfTables::new();
+            for item in items {
+                match toml_item(item, true)? {
+                    Item::Table(table) => tables.push(table),
+                    _ => unreachable!("objects become tables"),
+                }
+            }
+            Ok(Item::ArrayOfTables(tables))
+        }
+        value => toml_value(value).map(Item::Value),
+    }
+}
+
+fn toml_value(value: &Value) -> Result<toml_edit::Value, String> {
+    Ok(match value {
+        Value::Null => return Err("TOML has no empty value, use unset instead".to_string()),
+        Value::Bool(value) => (*value).into(),
+        Value::Number(number) => match number.as_i64() {
+            Some(integer) => integer.into(),
+            None => number.as_f64().unwrap_or_default().into(),
+        },
+        Value::String(text) => text.as_str().into(),
+        Value::Array(items) => toml_edit::Value::Array(items.iter().map(toml_value).collect::<Result<_, _>>()?),
+        Value::Object(map) => {
+            let mut table = toml_edit::InlineTable::new();
+            for (key, value) in map {
+                table.insert(key, toml_value(value)?);
+            }
+            toml_edit::Value::InlineTable(table)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const JSONC: &str = r##"{
+  // schema
+  "version": "0.2.0",
+  "theme": {
+    // header colours
+    "gradient": ["#ff0000", "#0000ff"],
+    // share of the terminal
+    "width": 0.8 // most of it
+  },
+  "modules": [
+    { "name": "CPU", "stats": ["name", "usage"] }
+  ]
+}
+"##;
+
+    fn edit(content: &str, edit: Edit) -> String {
+        let edited = edit_json(content, &edit).unwrap();
+        FileFormat::Jsonc.parse_str(&edited).unwrap();
+        edited
+    }
+
+    #[test]
+    fn unset_removes_the_comment_of_the_member() {
+        let edited = edit(JSONC, Edit::Unset("theme.gradient".to_string()));
+        assert!(!edited.contains("header colours"));
+        assert!(edited.contains("    // share of the terminal\n    \"width\": 0.8 // most of it\n  },"));
+    }
+
+    #[test]
+    fn unset_of_the_last_member_removes_its_separator() {
+        let edited = edit(JSONC, Edit::Unset("theme.width".to_string()));
+        assert!(!edited.contains("share of the terminal") && !edited.contains("most of it"));
+        assert!(edited.contains("\"gradient\": [\"#ff0000\", \"#0000ff\"]\n  },"));
+    }
+
+    #[test]
+    fn unset_on_one_line() {
+        let edited = edit(r#"{ "a": 1, "b": 2 }"#, Edit::Unset("a".to_string()));
+        assert_eq!(edited, r#"{ "b": 2 }"#);
+        let edited = edit(r#"{ "a": 1, "b": 2 }"#, Edit::Unset("b".to_string()));
+        assert_eq!(edited, r#"{ "a": 1 }"#);
+    }
+
+    #[test]
+    fn set_keeps_comments() {
+        let edited = edit(JSONC, Edit::Set("theme.width".to_string(), json!(0.5)));
+        assert!(edited.contains("    // share of the terminal\n    \"width\": 0.5 // most of it\n"));
+        let edited = edit(JSONC, Edit::Set("theme.align".to_string(), json!("left")));
+        assert!(edited.contains("    \"width\": 0.8, // most of it\n    \"align\": \"left\"\n  },"));
+        let edited = edit(JSONC, Edit::Set("timeout".to_string(), json!(500)));
+        assert_eq!(FileFormat::Jsonc.parse_str(&edited).unwrap()["timeout"], json!(500));
+        assert!(edited.starts_with("{\n  // schema\n"));
+    }
+
+    #[test]
+    fn set_and_unset_round_trip() {
+        let set = edit(JSONC, Edit::Set("theme.align".to_string(), json!("left")));
+        assert_eq!(edit(&set, Edit::Unset("theme.align".to_string())), JSONC);
+    }
+
+    #[test]
+    fn append_to_a_list() {
+        let edited = edit(JSONC, Edit::Append("modules".to_string(), json!({ "name": "Memory" })));
+        let value = FileFormat::Jsonc.parse_str(&edited).unwrap();
+        assert_eq!(value["modules"][1], json!({ "name": "Memory" }));
+        let edited = edit(JSONC, Edit::Append("modules[0].stats".to_string(), json!("cores")));
+        assert!(edited.contains(r#"["name", "usage", "cores"]"#));
+    }
+
+    #[test]
+    fn insert_after_a_trailing_comma() {
+        let content = "{\n  \"theme\": {\n    \"width\": 0.8, // most of it\n  },\n  \"modules\": [\n    { \"name\": \"CPU\" },\n  ],\n}\n";
+        let edited = edit(content, Edit::Set("theme.align".to_string(), json!("right")));
+        assert!(edited.contains("    \"width\": 0.8, // most of it\n    \"align\": \"right\",\n  },"));
+        let edited = edit(content, Edit::Append("modules".to_string(), json!({ "name": "Memory" })));
+        assert!(edited.contains("    { \"name\": \"CPU\" },\n    {\n      \"name\": \"Memory\"\n    },\n  ],"));
+        let edited = edit(r#"{ "stats": ["name", "usage",] }"#, Edit::Append("stats".to_string(), json!("cores")));
+        assert_eq!(edited, r#"{ "stats": ["name", "usage", "cores",] }"#);
+        let edited = edit(r#"{ "a": 1, }"#, Edit::Set("b".to_string(), json!(2)));
+        assert_eq!(edited, r#"{ "a": 1, "b": 2, }"#);
+    }
+
+    #[test]
+    fn toml_edits_keep_comments() {
+        let content = "# mine\n[theme]\n# share of the terminal\nwidth = 0.8 # most of it\n";
+        let edited = edit_toml(content, &Edit::Set("theme.width".to_string(), json!(0.5))).unwrap();
+        assert_eq!(edited, "# mine\n[theme]\n# share of the terminal\nwidth = 0.5 # most of it\n");
+        let edited = edit_toml(content, &Edit::Unset("theme.width".to_string())).unwrap();
+        assert_eq!(edited, "# mine\n[theme]\n");
+    }
+
+    #[test]
+    fn errors_name_the_path() {
+        assert_eq!(edit_json(JSONC, &Edit::Unset("theme.align".to_string())), Err(not_set("theme.align")));
+        assert!(edit_json(JSONC, &Edit::Append("theme".to_string(), json!(1))).is_err());
+    }
+
+    #[test]
+    fn rewrite_applies_the_difference() {
+        let before = FileFormat::Jsonc.parse_str(JSONC).unwrap();
+        let mut after = before.clone();
+        after["theme"].as_object_mut().unwrap().shift_remove("gradient");
+        after["modules"][0]["stats"] = json!(["name"]);
+        let rewritten = rewrite(JSONC, FileFormat::Jsonc, &before, &after).unwrap();
+        assert!(rewritten.contains("// schema") && rewritten.contains("// most of it"));
+        assert_eq!(FileFormat::Jsonc.parse_str(&rewritten).unwrap(), after);
+    }
+}
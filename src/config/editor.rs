@@ -0,0 +1,130 @@
+//! `ff --config`: edits the user's config file in the user's editor.
+//!
+//! The editor is `$VISUAL`, else `$EDITOR`, else whatever the platform
+//! opens files with. `ff` waits for it to exit and checks the file again;
+//! while it has problems the user is offered to edit it again or to go back
+//! to the last valid version, which is kept next to the file as
+//! `<file>.edit.bak`. `xdg-open` returns before the file is edited, so a
+//! file opened with it is left for `ff config check` to check.
+use crate::config::check::{self, Report};
+use crate::config::{backup_path, config_io, default_config, user_config_path, FileFormat, EDIT_BACKUP};
+use crate::error::Error;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::Path;
+use std::process::Command;
+use std::{env, fs};
+
+/// Opens the user file, creating it with the defaults if needed, and
+/// returns once it is valid or the user gives up. `show` prints the
+/// problems found after each edit.
+pub fn open_config(config_file: Option<&Path>, show: impl Fn(&Report)) -> Result<(), Error> {
+    let path = user_config_path(config_file)?;
+    if !path.exists() {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(config_io(dir))?;
+        }
+        let format = FileFormat::from_path(&path);
+        fs::write(&path, default_config(format)).map_err(config_io(&path))?;
+    }
+    let backup = backup_path(&path, EDIT_BACKUP);
+    if check::check_file(&path, &mut Report::default())?.is_some() {
+        fs::copy(&path, &backup).map_err(config_io(&backup))?;
+    }
+    loop {
+        if !run_editor(&path)? {
+            eprintln!(
+                "opened {} in the default application, which does not wait for the edit; run `ff config check` when you are done",
+                path.display()
+            );
+            return Ok(());
+        }
+        let mut report = Report::default();
+        if check::check_file(&path, &mut report)?.is_some() {
+            fs::copy(&path, &backup).map_err(config_io(&backup))?;
+            return Ok(());
+        }
+        show(&report);
+        let problems = Error::ConfigCheck {
+            problems: report.problems.len(),
+        };
+        if !io::stdin().is_terminal() {
+            return Err(problems);
+        }
+        let restore = backup.exists();
+        match ask(restore).as_str() {
+            "" | "e" | "edit" => continue,
+            "r" | "restore" if restore => {
+                fs::copy(&backup, &path).map_err(config_io(&path))?;
+                eprintln!("restored the last valid config from {}", backup.display());
+                return Ok(());
+            }
+            _ => return Err(problems),
+        }
+    }
+}
+
+/// Asks what to do about an invalid file. End of input means keep.
+fn ask(restore: bool) -> String {
+    if restore {
+        eprint!("[e]dit again, [r]estore the last valid config or [k]eep it as it is? [E/r/k] ");
+    } else {
+        eprint!("[e]dit again or [k]eep it as it is? [E/k] ");
+    }
+    let _ = io::stderr().flush();
+    let mut answer = String::new();
+    match io::stdin().lock().read_line(&mut answer) {
+        Ok(0) | Err(_) => "k".to_string(),
+        Ok(_) => answer.trim().to_lowercase(),
+    }
+}
+
+/// Runs the editor on `path` and waits for it to exit. Returns whether the
+/// editor was done with the file when it exited.
+fn run_editor(path: &Path) -> Result<bool, Error> {
+    let (mut command, waits) = editor_command();
+    command.arg(path);
+    let name = format!("{:?}", command.get_program());
+    let status = command
+        .status()
+        .map_err(|source| Error::Editor { command: name.clone(), source })?;
+    if !status.success() {
+        return Err(Error::Editor {
+            command: name,
+            source: io::Error::other(format!("the editor exited with {}", status)),
+        });
+    }
+    Ok(waits)
+}
+
+/// `$VISUAL` or `$EDITOR`, which may include arguments such as
+/// `code --wait`, else the platform's opener, and whether the command
+/// waits until the file is edited.
+fn editor_command() -> (Command, bool) {
+    for variable in ["VISUAL", "EDITOR"] {
+        if let Ok(editor) = env::var(variable) {
+            let mut words = editor.split_whitespace();
+            if let Some(program) = words.next() {
+                let mut command = Command::new(program);
+                command.args(words);
+                return (command, true);
+            }
+        }
+    }
+    #[cfg(target_os = "windows")]
+    {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", "/WAIT", ""]);
+        (command, true)
+    }
+    #[cfg(target_os = "macos")]
+    {
+        let mut command = Command::new("open");
+        command.args(["-W", "-t"]);
+        (command, true)
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        // hands the file to the desktop's editor and exits
+        (Command::new("xdg-open"), false)
+    }
+}
@@ -0,0 +1,281 @@
+//! Config values from environment variables, for per-host tweaks that do
+//! not belong in a shared file.
+//!
+//! Every key has a variable named after its path: `theme.width` is
+//! `FF_THEME_WIDTH` and `unknown_stats` is `FF_UNKNOWN_STATS`.
+//! `FF_MODULES=CPU,Disks` chooses the modules like `--modules` does, and
+//! the settings of a single module are addressed by its name, e.g.
+//! `FF_MODULE_CPU_STATS=name,usage`.
+//!
+//! Values are read as JSON when possible and as text otherwise; lists may
+//! also be written comma separated. Each variable becomes a layer of its
+//! own between the profile and the command line (see [`layers`]) that sets
+//! only its key, and is checked like a config file before it is used.
+//! Other `FF_` variables are ignored, with a notice for those that look
+//! like a misspelt one of ours.
+//!
+//! [`layers`]: super::layers
+use crate::config::check::{self, MODULE_KEYS};
+use crate::config::{edit, layers, profiles, Config, Layer, Origin};
+use crate::error::Error;
+use crate::modules;
+use crate::suggest::did_you_mean;
+use serde_json::{json, Value};
+use std::env;
+
+const PREFIX: &str = "FF_";
+const MODULES: &str = "FF_MODULES";
+const MODULE_PREFIX: &str = "FF_MODULE_";
+/// Variables that start with [`PREFIX`] but are not config keys.
+const RESERVED: &[&str] = &["FF_CONFIG", profiles::VARIABLE];
+
+/// The variable that overrides `path`, e.g. `FF_THEME_WIDTH`.
+pub fn variable(path: &str) -> String {
+    format!("{}{}", PREFIX, path.replace('.', "_").to_uppercase())
+}
+
+/// One layer for every variable that sets a config key, applied on top of
+/// `below`, the merged files and profile. Variables that look like ours
+/// but do not name a key are reported in `notices`.
+pub fn layers(below: &Value, notices: &mut Vec<String>) -> Result<Vec<Layer>, Error> {
+    variable_layers(below, env::vars(), notices)
+}
+
+/// [`layers`] for the variables in `vars`.
+fn variable_layers(
+    below: &Value,
+    vars: impl IntoIterator<Item = (String, String)>,
+    notices: &mut Vec<String>,
+) -> Result<Vec<Layer>, Error> {
+    let defaults = serde_json::to_value(Config::default()).expect("the default config always serializes");
+    let keys: Vec<String> = layers::leaves(&defaults)
+        .into_iter()
+        .map(|(path, _)| path)
+        .filter(|path| !path.starts_with("modules") && !path.starts_with("profiles"))
+        .collect();
+    let mut variables: Vec<(String, String)> = vars
+        .into_iter()
+        .filter(|(name, _)| name.starts_with(PREFIX) && !RESERVED.contains(&name.as_str()))
+        .collect();
+    // the module list first, so that module settings apply to the chosen modules
+    variables.sort_by_key(|(name, _)| (name != MODULES, name.clone()));
+    let mut modules = below.get("modules").cloned().unwrap_or_else(|| json!([]));
+    let mut layers = Vec::new();
+    for (name, text) in variables {
+        let invalid = |message: String| Error::Environment {
+            variable: name.clone(),
+            message,
+        };
+        let (value, path) = if name == MODULES {
+            modules = select(&modules, &text).map_err(invalid)?;
+            (json!({ "modules": modules }), None)
+        } else if let Some(key) = keys.iter().find(|key| variable(key) == name) {
+            let mut value = json!({});
+            *edit_path(&mut value, key) = parse(&text, false);
+            (value, Some(key.clone()))
+        } else if let Some((index, key)) = module_setting(&modules, &name) {
+            let setting = parse(&text, key == "stats");
+            modules[index][key] = setting.clone();
+            // the module is checked on its own, what the files set for it
+            // is not this variable's problem
+            let mut problems = Vec::new();
+            check::validate(&json!({ "modules": [modules[index]] }), &mut problems);
+            let path = format!("modules[0].{}", key);
+            if let Some((_, message)) = problems.into_iter().find(|(at, _)| at.starts_with(&path)) {
+                return Err(invalid(message));
+            }
+            (json!({ "modules": { index.to_string(): { key: setting } } }), None)
+        } else {
+            let known: Vec<String> = keys
+                .iter()
+                .map(|key| variable(key))
+                .chain([MODULES.to_string()])
+                .chain(module_variables(&modules))
+                .collect();
+            let suggestion = did_you_mean(&name, known.iter().map(String::as_str));
+            if !suggestion.is_empty() {
+                notices.push(format!(
+                    "ignoring the environment variable {}, it does not name a config key{}",
+                    name, suggestion
+                ));
+            }
+            continue;
+        };
+        if let Some(path) = path {
+            let mut problems = Vec::new();
+            check::validate(&value, &mut problems);
+            if let Some((_, message)) = problems.into_iter().find(|(at, _)| at.starts_with(&path)) {
+                return Err(invalid(message));
+            }
+        }
+        layers.push(Layer {
+            origin: Origin::Environment(name),
+            value,
+        });
+    }
+    Ok(layers)
+}
+
+/// The configured modules called `names` (comma separated), in that order.
+/// Built-in modules that are not configured get all of their stats.
+fn select(modules: &Value, names: &str) -> Result<Value, String> {
+    let configured = modules.as_array().map_or(&[][..], Vec::as_slice);
+    let name_of = |module: &Value| module.get("name").and_then(Value::as_str).map(String::from);
+    let mut selected = Vec::new();
+    for name in names.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+        match configured.iter().find(|module| name_of(module).as_deref() == Some(name)) {
+            Some(module) => selected.push(module.clone()),
+            None => match modules::find(name) {
+                Some(info) => selected.push(json!({ "name": info.name, "stats": info.stat_names().collect::<Vec<_>>() })),
+                None => {
+                    let known: Vec<String> = configured.iter().filter_map(name_of).collect();
+                    let known = known.iter().map(String::as_str).chain(modules::REGISTRY.iter().map(|info| info.name));
+                    return Err(format!("unknown module `{}`{}", name, did_you_mean(name, known)));
+                }
+            },
+        }
+    }
+    Ok(Value::Array(selected))
+}
+
+/// The index of the module and the key that `FF_MODULE_<NAME>_<KEY>` sets.
+fn module_setting(modules: &Value, name: &str) -> Option<(usize, &'static str)> {
+    let rest = name.strip_prefix(MODULE_PREFIX)?;
+    modules.as_array()?.iter().enumerate().find_map(|(index, module)| {
+        let key = rest.strip_prefix(&module_part(module)?)?.strip_prefix('_')?;
+        MODULE_KEYS
+            .iter()
+            .find(|known| **known != "name" && known.to_uppercase() == key)
+            .map(|known| (index, *known))
+    })
+}
+
+/// Every `FF_MODULE_<NAME>_<KEY>` variable for `modules`.
+fn module_variables(modules: &Value) -> Vec<String> {
+    let modules = modules.as_array().map_or(&[][..], Vec::as_slice);
+    modules
+        .iter()
+        .filter_map(module_part)
+        .flat_map(|module| {
+            MODULE_KEYS
+                .iter()
+                .filter(|key| **key != "name")
+                .map(move |key| format!("{}{}_{}", MODULE_PREFIX, module, key.to_uppercase()))
+        })
+        .collect()
+}
+
+/// The name of `module` as it is written in variable names, e.g. `CPU`.
+fn module_part(module: &Value) -> Option<String> {
+    let name = module.get("name")?.as_str()?;
+    Some(
+        name.chars()
+            .map(|char| if char.is_ascii_alphanumeric() { char.to_ascii_uppercase() } else { '_' })
+            .collect(),
+    )
+}
+
+/// The value of a variable. Lists may be written without brackets.
+fn parse(text: &str, list: bool) -> Value {
+    if list && !text.trim_start().starts_with('[') {
+        return text
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(|item| Value::String(item.to_string()))
+            .collect();
+    }
+    edit::parse_value(text)
+}
+
+/// The value at a dotted `path` in `value`, creating tables on the way.
+fn edit_path<'a>(value: &'a mut Value, path: &str) -> &'a mut Value {
+    path.split('.').fold(value, |value, key| &mut value[key])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::layers::merge;
+
+    fn below() -> Value {
+        json!({
+            "theme": { "width": 0.5 },
+            "modules": [
+                { "name": "CPU", "stats": ["name"] },
+                { "name": "Memory", "stats": ["total"], "timeout": 100 }
+            ]
+        })
+    }
+
+    /// The merged config and the value of each layer.
+    fn apply(vars: &[(&str, &str)]) -> Result<(Value, Vec<Value>, Vec<String>), String> {
+        let vars = vars.iter().map(|(name, value)| (name.to_string(), value.to_string()));
+        let mut notices = Vec::new();
+        let mut sources = vec![Layer { origin: Origin::Default, value: below() }];
+        let layers = variable_layers(&below(), vars, &mut notices).map_err(|err| err.to_string())?;
+        sources.extend(layers);
+        let (value, _) = merge(&sources);
+        Ok((value, sources.into_iter().skip(1).map(|layer| layer.value).collect(), notices))
+    }
+
+    #[test]
+    fn keys_are_named_after_their_path() {
+        assert_eq!(variable("theme.width"), "FF_THEME_WIDTH");
+        assert_eq!(variable("unknown_stats"), "FF_UNKNOWN_STATS");
+        let (value, layers, _) = apply(&[("FF_THEME_WIDTH", "0.8"), ("FF_THEME_ALIGN", "right")]).unwrap();
+        assert_eq!(value["theme"]["width"], json!(0.8));
+        assert_eq!(value["theme"]["align"], json!("right"));
+        assert_eq!(layers[1], json!({ "theme": { "width": 0.8 } }));
+    }
+
+    #[test]
+    fn module_settings_change_their_key_only() {
+        let (value, layers, _) = apply(&[("FF_MODULE_MEMORY_STATS", "used, free")]).unwrap();
+        assert_eq!(layers[0], json!({ "modules": { "1": { "stats": ["used", "free"] } } }));
+        assert_eq!(value["modules"][1], json!({ "name": "Memory", "stats": ["used", "free"], "timeout": 100 }));
+        assert_eq!(value["modules"][0], below()["modules"][0]);
+
+        let variable = Origin::Environment("FF_MODULE_MEMORY_STATS".to_string());
+        let sources = [
+            Layer { origin: Origin::Default, value: below() },
+            Layer { origin: variable.clone(), value: layers[0].clone() },
+        ];
+        let (_, origins) = merge(&sources);
+        assert_eq!(origins.get("modules[1].stats[0]"), Some(&variable));
+        assert_eq!(origins.get("modules[1].name"), Some(&Origin::Default));
+        assert_eq!(origins.get("modules[0].stats"), Some(&Origin::Default));
+    }
+
+    #[test]
+    fn module_list_comes_first() {
+        let (value, _, _) = apply(&[("FF_MODULE_DISKS_TIMEOUT", "50"), ("FF_MODULES", "Disks,CPU")]).unwrap();
+        assert_eq!(value["modules"][0]["name"], json!("Disks"));
+        assert_eq!(value["modules"][0]["timeout"], json!(50));
+        assert_eq!(value["modules"][1], below()["modules"][0]);
+        assert!(apply(&[("FF_MODULES", "CPU,Memroy")]).unwrap_err().contains("did you mean `Memory`?"));
+    }
+
+    #[test]
+    fn invalid_values_name_the_variable() {
+        let err = apply(&[("FF_MODULE_CPU_STATS", "name,speeed")]).unwrap_err();
+        assert!(err.contains("FF_MODULE_CPU_STATS") && err.contains("speeed"), "{}", err);
+        assert!(apply(&[("FF_THEME_WIDTH", "wide")]).is_err());
+    }
+
+    #[test]
+    fn only_misspelt_variables_get_a_notice() {
+        let (_, layers, notices) = apply(&[
+            ("FF_THEME_WIDHT", "0.8"),
+            ("FF_DEBUG_TOKEN", "1"),
+            ("FF_CONFIG", "/tmp/ff.json"),
+            ("HOME", "/root"),
+        ])
+        .unwrap();
+        assert!(layers.is_empty());
+        assert_eq!(
+            notices,
+            ["ignoring the environment variable FF_THEME_WIDHT, it does not name a config key, did you mean `FF_THEME_WIDTH`?"]
+        );
+    }
+}
@@ -0,0 +1,384 @@
+//! Config files may be written as JSON, JSON with comments, TOML or YAML.
+//! The format is chosen by file extension and every format is parsed into
+//! the same `serde_json::Value` before it is merged and validated.
+use crate::config::{locate, ConfigError};
+use serde_json::Value;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileFormat {
+    Json,
+    /// JSON that may contain `//` and `/* */` comments and trailing commas.
+    Jsonc,
+    Toml,
+    Yaml,
+}
+
+/// Extensions looked for, in order of preference, when searching a
+/// directory for a config file.
+pub const EXTENSIONS: [&str; 5] = ["toml", "yaml", "yml", "jsonc", "json"];
+
+impl FileFormat {
+    /// Format of `path` judged by its extension. Unknown extensions are
+    /// read as JSON.
+    pub fn from_path(path: &Path) -> FileFormat {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) => FileFormat::parse(extension).unwrap_or(FileFormat::Json),
+            None => FileFormat::Json,
+        }
+    }
+    pub fn parse(name: &str) -> Option<FileFormat> {
+        match name.to_ascii_lowercase().as_str() {
+            "json" => Some(FileFormat::Json),
+            "jsonc" => Some(FileFormat::Jsonc),
+            "toml" => Some(FileFormat::Toml),
+            "yaml" | "yml" => Some(FileFormat::Yaml),
+            _ => None,
+        }
+    }
+    pub fn parse_str(&self, content: &str) -> Result<Value, ConfigError> {
+        self.parse_located(content).map_err(|(message, location)| ConfigError {
+            path: ".".to_string(),
+            message: match location {
+                Some((line, column)) => format!("{} at line {} column {}", message, line, column),
+                None => message,
+            },
+        })
+    }
+    /// Like [`parse_str`](Self::parse_str), but a syntax error comes with
+    /// the 1-based line and column it was found at, when known, rather than
+    /// with them in its message.
+    pub fn parse_located(&self, content: &str) -> Result<Value, (String, Option<(usize, usize)>)> {
+        match self {
+            FileFormat::Json => serde_json::from_str(content).map_err(json_error),
+            FileFormat::Jsonc => serde_json::from_str(&strip_jsonc(content)).map_err(json_error),
+            FileFormat::Toml => toml::from_str(content).map_err(|err| {
+                let location = err.span().map(|span| locate::line_column(content, span.start));
+                (err.message().to_string(), location)
+            }),
+            FileFormat::Yaml => serde_yaml::from_str(content).map_err(|err| {
+                let location = err.location().map(|location| (location.line(), location.column()));
+                let message = err.to_string();
+                let message = match location {
+                    Some((line, column)) => message.replace(&format!(" at line {} column {}", line, column), ""),
+                    None => message,
+                };
+                (message, location)
+            }),
+        }
+    }
+}
+
+fn json_error(err: serde_json::Error) -> (String, Option<(usize, usize)>) {
+    let message = err.to_string();
+    if err.line() == 0 {
+        return (message, None);
+    }
+    let position = format!(" at line {} column {}", err.line(), err.column());
+    let message = message.strip_suffix(&position).unwrap_or(&message).to_string();
+    (message, Some((err.line(), err.column())))
+}
+
+/// Blanks out comments and trailing commas so the result can be read by
+/// `serde_json`. Everything is replaced by spaces rather than removed, one
+/// per byte, so that positions in the result match the original file.
+pub fn strip_jsonc(content: &str) -> String {
+    let chars: Vec<char> = content.chars().collect();
+    let mut out = String::with_capacity(content.len());
+    let mut index = 0;
+    let mut in_string = false;
+    // position in `out` of a comma that may turn out to be trailing
+    let mut pending_comma: Option<usize> = None;
+    while index < chars.len() {
+        let char = chars[index];
+        let next = chars.get(index + 1).copied();
+        if in_string {
+            out.push(char);
+            if char == '\\' {
+                if let Some(next) = next {
+                    out.push(next);
+                    index += 1;
+                }
+            } else if char == '"' {
+                in_string = false;
+            }
+        } else if char == '/' && next == Some('/') {
+            while index < chars.len() && chars[index] != '\n' {
+                out.push_str(&" ".repeat(chars[index].len_utf8()));
+                index += 1;
+            }
+            continue;
+        } else if char == '/' && next == Some('*') {
+            out.push_str("  ");
+            index += 2;
+            while index < chars.len() && !(chars[index] == '*' && chars.get(index + 1) == Some(&'/')) {
+                match chars[index] {
+                    '\n' => out.push('\n'),
+                    char => out.push_str(&" ".repeat(char.len_utf8())),
+                }
+                index += 1;
+            }
+            out.push_str("  ");
+            index += 2;
+            continue;
+        } else {
+            match char {
+                '"' => {
+                    in_string = true;
+                    pending_comma = None;
+                }
+                ',' => pending_comma = Some(out.len()),
+                '}' | ']' => {
+                    if let Some(comma) = pending_comma.take() {
+                        out.replace_range(comma..comma + 1, " ");
+                    }
+                }
+                char if char.is_whitespace() => {}
+                _ => pending_comma = None,
+            }
+            out.push(char);
+        }
+        index += 1;
+    }
+    out
+}
+
+/// Explanations written above each key of a generated config file.
+const COMMENTS: &[(&str, &str)] = &[
+    ("version", "Version of the config schema. Older files are upgraded automatically."),
+    ("theme", "Colours are written as \"#RRGGBB\" strings or 0xRRGGBB integers."),
+    ("theme.gradient", "Start and end colour of the gradient."),
+    ("theme.text", "Colours of module headers, group sub-headers (e.g. one per disk),\nstat names (key) and their values (value)."),
+    ("theme.width", "Fraction of the terminal width used by the output, greater than 0.0 and at most 1.0."),
+    ("theme.align", "Position of the output in the terminal: left, right or center."),
+    ("timeout", "Milliseconds to wait for a module before showing it as timed out."),
+    ("unknown_stats", "Stats a module does not know are reported as warnings and then left out (hide)\nor shown with \"unknown\" as their value (show)."),
+    (
+        "modules",
+        "Modules in the order they are shown. `icon` is a hexadecimal code point,\n\
+         `stats` lists the values to show and `unavailable` (hide or show) decides\n\
+         what happens on machines without that hardware. A module with a `plugin`\n\
+         path runs that executable instead of a built-in module.",
+    ),
+    (
+        "profiles",
+        "Named sets of theme, timeout, unknown_stats and modules settings, chosen with\n\
+         --profile NAME or FF_PROFILE=NAME. A profile inherits everything it does not\n\
+         set from the settings above, or from the profile named by its `extends`.",
+    ),
+];
+
+fn comment(path: &str) -> Option<&'static str> {
+    COMMENTS.iter().find(|(key, _)| *key == path).map(|(_, comment)| *comment)
+}
+
+/// Writes `value` in `format`, with the explanations from [`COMMENTS`] for
+/// every format that supports comments.
+pub fn render(value: &Value, format: FileFormat) -> String {
+    let mut out = String::new();
+    match format {
+        FileFormat::Json => {
+            out = serde_json::to_string_pretty(value).expect("config values always serialize");
+            out.push('\n');
+        }
+        FileFormat::Jsonc => {
+            write_jsonc(&mut out, value, "", 0);
+            out.push('\n');
+        }
+        FileFormat::Toml => write_toml_table(&mut out, value, ""),
+        FileFormat::Yaml => write_yaml(&mut out, value, "", 0),
+    }
+    out
+}
+
+pub(super) fn scalar(value: &Value) -> String {
+    match value {
+        Value::Array(items) => format!("[{}]", items.iter().map(scalar).collect::<Vec<_>>().join(", ")),
+        value => value.to_string(),
+    }
+}
+
+pub(super) fn is_inline(value: &Value) -> bool {
+    match value {
+        Value::Object(_) => false,
+        Value::Array(items) => items.iter().all(|item| !item.is_object() && !item.is_array()),
+        _ => true,
+    }
+}
+
+fn write_comment(out: &mut String, path: &str, indent: &str, marker: &str) {
+    if let Some(comment) = comment(path) {
+        for line in comment.lines() {
+            out.push_str(&format!("{}{} {}\n", indent, marker, line));
+        }
+    }
+}
+
+fn write_jsonc(out: &mut String, value: &Value, path: &str, depth: usize) {
+    let indent = "    ".repeat(depth + 1);
+    let close = "    ".repeat(depth);
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            out.push_str("{\n");
+            for (index, (key, value)) in map.iter().enumerate() {
+                let path = super::layers::join(path, key);
+                write_comment(out, &path, &indent, "//");
+                out.push_str(&format!("{}{}: ", indent, Value::from(key.as_str())));
+                write_jsonc(out, value, &path, depth + 1);
+                out.push_str(if index + 1 < map.len() { ",\n" } else { "\n" });
+            }
+            out.push_str(&close);
+            out.push('}');
+        }
+        Value::Array(items) if !is_inline(value) => {
+            out.push_str("[\n");
+            for (index, item) in items.iter().enumerate() {
+                out.push_str(&indent);
+                write_jsonc(out, item, &format!("{}[{}]", path, index), depth + 1);
+                out.push_str(if index + 1 < items.len() { ",\n" } else { "\n" });
+            }
+            out.push_str(&close);
+            out.push(']');
+        }
+        value => out.push_str(&scalar(value)),
+    }
+}
+
+fn write_toml_table(out: &mut String, value: &Value, path: &str) {
+    let Value::Object(map) = value else {
+        return;
+    };
+    for (key, value) in map {
+        if value.is_null() || !is_inline(value) {
+            continue;
+        }
+        let path = super::layers::join(path, key);
+        write_comment(out, &path, "", "#");
+        out.push_str(&format!("{} = {}\n", toml_key(key), scalar(value)));
+    }
+    for (key, value) in map {
+        let child = super::layers::join(path, key);
+        match value {
+            Value::Object(_) => {
+                out.push('\n');
+                write_comment(out, &child, "", "#");
+                out.push_str(&format!("[{}]\n", toml_path(&child)));
+                write_toml_table(out, value, &child);
+            }
+            Value::Array(items) if !is_inline(value) => {
+                out.push('\n');
+                write_comment(out, &child, "", "#");
+                for item in items {
+                    out.push_str(&format!("[[{}]]\n", toml_path(&child)));
+                    write_toml_table(out, item, &child);
+                    out.push('\n');
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn toml_key(key: &str) -> String {
+    if !key.is_empty() && key.chars().all(|char| char.is_ascii_alphanumeric() || char == '_' || char == '-') {
+        key.to_string()
+    } else {
+        Value::from(key).to_string()
+    }
+}
+
+fn toml_path(path: &str) -> String {
+    path.split('.').map(toml_key).collect::<Vec<_>>().join(".")
+}
+
+fn write_yaml(out: &mut String, value: &Value, path: &str, depth: usize) {
+    let indent = "  ".repeat(depth);
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let path = super::layers::join(path, key);
+                write_comment(out, &path, &indent, "#");
+                if is_inline(value) || value.as_object().is_some_and(|map| map.is_empty()) {
+                    out.push_str(&format!("{}{}: {}\n", indent, key, scalar(value)));
+                } else {
+                    out.push_str(&format!("{}{}:\n", indent, key));
+                    write_yaml(out, value, &path, depth + 1);
+                }
+            }
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                let path = format!("{}[{}]", path, index);
+                let mut item_out = String::new();
+                write_yaml(&mut item_out, item, &path, depth + 1);
+                // the first line of the item shares its line with the dash
+                let item_out = item_out.trim_start();
+                out.push_str(&format!("{}- {}", indent, item_out));
+            }
+        }
+        value => out.push_str(&format!("{}{}\n", indent, scalar(value))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use serde_json::json;
+
+    const FORMATS: [FileFormat; 4] = [FileFormat::Json, FileFormat::Jsonc, FileFormat::Toml, FileFormat::Yaml];
+
+    #[test]
+    fn comments_inside_strings_are_kept() {
+        let content = r#"{
+  "a": "// not a comment", /* a comment */
+  "b": "say \"hi\" // still text", // a comment
+  "c": "/* text */"
+}"#;
+        let stripped = strip_jsonc(content);
+        assert_eq!(stripped.len(), content.len());
+        assert_eq!(stripped.lines().count(), content.lines().count());
+        assert_eq!(
+            FileFormat::Jsonc.parse_str(content).unwrap(),
+            json!({ "a": "// not a comment", "b": "say \"hi\" // still text", "c": "/* text */" })
+        );
+    }
+
+    #[test]
+    fn trailing_commas_are_dropped() {
+        let content = "{\n  \"a\": [1, 2,],\n  \"b\": \",}\", // after a comma\n  /* and\n     more */\n}";
+        assert_eq!(FileFormat::Jsonc.parse_str(content).unwrap(), json!({ "a": [1, 2], "b": ",}" }));
+        assert_eq!(
+            strip_jsonc(content).lines().nth(1).map(str::to_string),
+            Some("  \"a\": [1, 2 ],".to_string())
+        );
+        assert!(FileFormat::Json.parse_str(content).is_err());
+    }
+
+    #[test]
+    fn syntax_errors_have_a_location() {
+        let (message, location) = FileFormat::Jsonc.parse_located("{\n  \"a\": 1\n  \"b\": 2\n}").unwrap_err();
+        assert_eq!((message.as_str(), location), ("expected `,` or `}`", Some((3, 3))));
+        assert_eq!(
+            FileFormat::Json.parse_str("[1,]").unwrap_err().message,
+            "trailing comma at line 1 column 4"
+        );
+    }
+
+    #[test]
+    fn rendered_files_read_back() {
+        let defaults = serde_json::to_value(Config::default()).unwrap();
+        let mut tricky = defaults.clone();
+        tricky["modules"][0]["stats"] = json!(["a \"quoted\" # stat", "// not a comment", "ünïcode: yes"]);
+        for format in FORMATS {
+            for value in [&defaults, &tricky] {
+                let text = render(value, format);
+                assert_eq!(&format.parse_str(&text).unwrap(), value, "{:?}:\n{}", format, text);
+            }
+        }
+        assert!(render(&defaults, FileFormat::Jsonc).contains("  // Fraction of the terminal width"));
+        assert!(render(&defaults, FileFormat::Toml).contains("# Fraction of the terminal width"));
+        assert!(render(&defaults, FileFormat::Yaml).contains("# Fraction of the terminal width"));
+        assert!(!render(&defaults, FileFormat::Json).contains("Fraction"));
+    }
+}
@@ -0,0 +1,312 @@
+//! Config files are read from several places and merged in this order, each
+//! layer overriding the ones before it:
+//!
+//! 1. built-in defaults
+//! 2. the system file, `/etc/ninju_fastfestch/config.json`
+//! 3. the user file: `--config-file=PATH`, else `$FF_CONFIG`, else
+//!    `$XDG_CONFIG_HOME/ninju_fastfestch/config.json` (`~/.config/...` when
+//!    `XDG_CONFIG_HOME` is unset, `%LOCALAPPDATA%\ninju_fastfestch` on Windows)
+//! 4. the project file, `.ff.json` in the current directory
+//! 5. the selected profile, see [`profiles`](super::profiles)
+//! 6. environment variables such as `FF_THEME_WIDTH` (see
+//!    [`environment`](super::environment))
+//! 7. command line flags such as `--width`
+//!
+//! Each file may also be written as `.toml`, `.yaml`, `.yml` or `.jsonc`
+//! (see [`formats`](super::formats)); when several exist in one directory
+//! the first in [`EXTENSIONS`] wins.
+//!
+//! Objects are merged key by key; every other value, including arrays such
+//! as `modules`, replaces the value of the previous layer as a whole. A
+//! layer changes single items of an array with an object keyed by their
+//! index instead, e.g. `{"modules": {"1": {"stats": ["name"]}}}`, as the
+//! environment variables for one module do.
+use crate::config::formats::EXTENSIONS;
+use crate::error::Error;
+use serde_json::{Map, Value};
+use std::{
+    collections::BTreeMap, env, fmt, io, path::{Path, PathBuf}
+};
+
+pub const APP_DIR: &str = "ninju_fastfestch";
+pub const CONFIG_FILE: &str = "config.json";
+const CONFIG_STEM: &str = "config";
+const PROJECT_STEM: &str = ".ff";
+
+/// Where a layer, and therefore every value it sets, came from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Origin {
+    Default,
+    System(PathBuf),
+    User(PathBuf),
+    Project(PathBuf),
+    /// The name of the selected profile or of one it extends.
+    Profile(String),
+    /// The name of the environment variable.
+    Environment(String),
+    CommandLine,
+}
+
+impl Origin {
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Origin::System(path) | Origin::User(path) | Origin::Project(path) => Some(path),
+            Origin::Default | Origin::Profile(_) | Origin::Environment(_) | Origin::CommandLine => None,
+        }
+    }
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Origin::Default => f.write_str("built-in default"),
+            Origin::System(path) | Origin::User(path) | Origin::Project(path) => {
+                write!(f, "{}", path.display())
+            }
+            Origin::Profile(name) => write!(f, "profile {}", name),
+            Origin::Environment(variable) => write!(f, "environment variable {}", variable),
+            Origin::CommandLine => f.write_str("command line"),
+        }
+    }
+}
+
+pub struct Layer {
+    pub origin: Origin,
+    pub value: Value,
+}
+
+/// Records which layer supplied each value of the merged config, keyed by
+/// paths like `theme.width` or `modules[1].stats`.
+#[derive(Debug, Default)]
+pub struct Origins {
+    paths: BTreeMap<String, Origin>,
+}
+
+impl Origins {
+    /// Origin of the value at `path`, or of the closest enclosing value that
+    /// was replaced as a whole (an array, for instance).
+    pub fn get(&self, path: &str) -> Option<&Origin> {
+        let mut path = path;
+        loop {
+            if let Some(origin) = self.paths.get(path) {
+                return Some(origin);
+            }
+            path = &path[..path.rfind(['.', '['])?];
+        }
+    }
+}
+
+/// Merges `layers` in order and records where every value came from.
+pub fn merge(layers: &[Layer]) -> (Value, Origins) {
+    let mut merged = Value::Object(Map::new());
+    let mut origins = Origins::default();
+    for layer in layers {
+        merge_value(&mut merged, &layer.value, "", &layer.origin, &mut origins);
+    }
+    (merged, origins)
+}
+
+fn merge_value(target: &mut Value, layer: &Value, path: &str, origin: &Origin, origins: &mut Origins) {
+    match (target, layer) {
+        (Value::Object(target), Value::Object(layer)) => {
+            for (key, value) in layer {
+                let path = join(path, key);
+                let target = target.entry(key.clone()).or_insert(Value::Null);
+                merge_value(target, value, &path, origin, origins);
+            }
+        }
+        (Value::Array(items), Value::Object(layer)) if is_item_patch(items, layer) => {
+            for (key, value) in layer {
+                let index: usize = key.parse().expect("checked by is_item_patch");
+                let path = format!("{}[{}]", path, index);
+                merge_value(&mut items[index], value, &path, origin, origins);
+            }
+        }
+        (target, layer) => {
+            *target = layer.clone();
+            origins
+                .paths
+                .retain(|known, _| !(known.starts_with(path) && is_nested(&known[path.len()..])));
+            origins.paths.insert(path.to_string(), origin.clone());
+        }
+    }
+}
+
+/// Whether `layer` changes items of `items` by index rather than replacing
+/// the array.
+fn is_item_patch(items: &[Value], layer: &Map<String, Value>) -> bool {
+    !layer.is_empty()
+        && layer
+            .keys()
+            .all(|key| key.parse::<usize>().is_ok_and(|index| index < items.len()))
+}
+
+fn is_nested(rest: &str) -> bool {
+    rest.is_empty() || rest.starts_with('.') || rest.starts_with('[')
+}
+
+pub fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+/// One step of a path such as `modules[1].stats`.
+#[derive(Clone, Copy)]
+pub enum Segment<'a> {
+    Key(&'a str),
+    Index(usize),
+}
+
+/// Splits a path written like those of [`leaves`] into its steps.
+pub fn segments(path: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    for part in path.split('.').filter(|part| !part.is_empty()) {
+        let (key, indices) = part.split_at(part.find('[').unwrap_or(part.len()));
+        if !key.is_empty() {
+            segments.push(Segment::Key(key));
+        }
+        for index in indices.split(['[', ']']).filter(|index| !index.is_empty()) {
+            if let Ok(index) = index.parse() {
+                segments.push(Segment::Index(index));
+            }
+        }
+    }
+    segments
+}
+
+/// Flattens `value` into `(path, leaf)` pairs in document order.
+pub fn leaves(value: &Value) -> Vec<(String, &Value)> {
+    fn walk<'a>(value: &'a Value, path: String, out: &mut Vec<(String, &'a Value)>) {
+        match value {
+            Value::Object(map) if !map.is_empty() => {
+                for (key, value) in map {
+                    walk(value, join(&path, key), out);
+                }
+            }
+            Value::Array(items) if !items.is_empty() => {
+                for (index, value) in items.iter().enumerate() {
+                    walk(value, format!("{}[{}]", path, index), out);
+                }
+            }
+            _ => out.push((path, value)),
+        }
+    }
+    let mut out = Vec::new();
+    walk(value, String::new(), &mut out);
+    out
+}
+
+/// The first existing `{stem}.{extension}` in `dir`.
+pub fn find_config(dir: &Path, stem: &str) -> Option<PathBuf> {
+    EXTENSIONS
+        .iter()
+        .map(|extension| dir.join(format!("{}.{}", stem, extension)))
+        .find(|path| path.is_file())
+}
+
+pub fn system_config_path() -> Option<PathBuf> {
+    #[cfg(target_os = "windows")]
+    let dir = Path::new(&env::var_os("ProgramData")?).join(APP_DIR);
+    #[cfg(not(target_os = "windows"))]
+    let dir = Path::new("/etc").join(APP_DIR);
+    find_config(&dir, CONFIG_STEM)
+}
+
+/// The file explicitly requested with `--config-file` or `$FF_CONFIG`.
+pub fn explicit_config_path(config_file: Option<&Path>) -> Option<PathBuf> {
+    config_file
+        .map(Path::to_path_buf)
+        .or_else(|| env::var_os("FF_CONFIG").filter(|path| !path.is_empty()).map(PathBuf::from))
+}
+
+/// The existing user file in [`user_config_dir`], if there is one.
+pub fn user_config_file() -> Result<Option<PathBuf>, Error> {
+    Ok(find_config(&user_config_dir()?, CONFIG_STEM))
+}
+
+/// Directory of the default user file.
+pub fn user_config_dir() -> Result<PathBuf, Error> {
+    #[cfg(target_os = "windows")]
+    {
+        Ok(Path::new(&env_dir("LOCALAPPDATA")?).join(APP_DIR))
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        if let Some(xdg) = env::var_os("XDG_CONFIG_HOME").filter(|dir| Path::new(dir).is_absolute()) {
+            return Ok(Path::new(&xdg).join(APP_DIR));
+        }
+        let home = env_dir("HOME")?;
+        let legacy = Path::new(&home).join(APP_DIR);
+        let xdg = Path::new(&home).join(".config").join(APP_DIR);
+        // keep reading the pre-XDG location until the user moves the file
+        if find_config(&legacy, CONFIG_STEM).is_some() && find_config(&xdg, CONFIG_STEM).is_none() {
+            return Ok(legacy);
+        }
+        Ok(xdg)
+    }
+}
+
+fn env_dir(name: &str) -> Result<std::ffi::OsString, Error> {
+    env::var_os(name).filter(|dir| !dir.is_empty()).ok_or_else(|| Error::ConfigIo {
+        path: PathBuf::from(format!("${}", name)),
+        source: io::Error::new(io::ErrorKind::NotFound, "environment variable is not set"),
+    })
+}
+
+pub fn project_config_path() -> Option<PathBuf> {
+    find_config(&env::current_dir().ok()?, PROJECT_STEM)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn layer(origin: Origin, value: Value) -> Layer {
+        Layer { origin, value }
+    }
+
+    #[test]
+    fn objects_merge_and_arrays_replace() {
+        let (merged, origins) = merge(&[
+            layer(Origin::Default, json!({ "theme": { "width": 0.5, "headers": "blue" }, "modules": [{ "name": "CPU" }, { "name": "Memory" }] })),
+            layer(Origin::CommandLine, json!({ "theme": { "width": 0.8 }, "modules": [{ "name": "Disks" }] })),
+        ]);
+        assert_eq!(merged, json!({ "theme": { "width": 0.8, "headers": "blue" }, "modules": [{ "name": "Disks" }] }));
+        assert_eq!(origins.get("theme.width"), Some(&Origin::CommandLine));
+        assert_eq!(origins.get("theme.headers"), Some(&Origin::Default));
+        assert_eq!(origins.get("modules[0].name"), Some(&Origin::CommandLine));
+        assert_eq!(origins.get("modules[1].name"), Some(&Origin::CommandLine));
+    }
+
+    #[test]
+    fn replacing_a_value_forgets_the_origins_below_it() {
+        let (merged, origins) = merge(&[
+            layer(Origin::Default, json!({ "theme": { "text": { "groups": "red" } } })),
+            layer(Origin::Environment("FF_THEME_TEXT".to_string()), json!({ "theme": { "text": "green" } })),
+            layer(Origin::Profile("work".to_string()), json!({ "theme": { "textual": 1 } })),
+        ]);
+        assert_eq!(merged, json!({ "theme": { "text": "green", "textual": 1 } }));
+        assert_eq!(origins.get("theme.text.groups"), Some(&Origin::Environment("FF_THEME_TEXT".to_string())));
+        assert_eq!(origins.get("theme.textual"), Some(&Origin::Profile("work".to_string())));
+        assert_eq!(origins.get("timeout"), None);
+    }
+
+    #[test]
+    fn paths_round_trip() {
+        let value = json!({ "theme": { "width": 0.5 }, "modules": [{ "stats": ["name"] }], "empty": [] });
+        let paths: Vec<_> = leaves(&value).into_iter().map(|(path, _)| path).collect();
+        assert_eq!(paths, ["theme.width", "modules[0].stats[0]", "empty"]);
+        let steps: Vec<_> = segments("modules[0].stats[1]")
+            .into_iter()
+            .map(|segment| match segment {
+                Segment::Key(key) => key.to_string(),
+                Segment::Index(index) => index.to_string(),
+            })
+            .collect();
+        assert_eq!(steps, ["modules", "0", "stats", "1"]);
+    }
+}
@@ -0,0 +1,338 @@
+//! Finds where a value lives in the text of a config file, so that problems
+//! found in the parsed [`Value`](serde_json::Value) can be reported with a
+//! line and column.
+//!
+//! Paths are written like those of [`layers`](super::layers), e.g.
+//! `modules[1].stats[0]`. Object members are located at their key and
+//! array items at their first character. When part of a path cannot be
+//! found (a key that was added by a migration, for instance) the location
+//! of the closest enclosing value is returned instead.
+use crate::config::formats::{strip_jsonc, FileFormat};
+use crate::config::layers::{segments, Segment};
+use std::ops::Range;
+
+/// Byte offset of the value at `path` in `content`.
+pub fn locate(content: &str, format: FileFormat, path: &str) -> Option<usize> {
+    match format {
+        FileFormat::Json | FileFormat::Jsonc => locate_json(&strip_jsonc(content), path),
+        FileFormat::Toml => locate_toml(content, path),
+        FileFormat::Yaml => locate_yaml(content, path),
+    }
+}
+
+/// 1-based line and column (in characters) of `offset`.
+pub fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+}
+
+fn locate_json(content: &str, path: &str) -> Option<usize> {
+    let (found, _) = walk_json(content, path);
+    found.last().map(|(start, _)| *start).or(Some(skip_whitespace(content.as_bytes(), 0)))
+}
+
+/// Where the member at `path` sits in JSON `content` (with any comments
+/// already blanked out): the start of the member, which is its key inside
+/// objects, and the range of its value. `None` unless the whole path
+/// exists.
+pub fn json_member(content: &str, path: &str) -> Option<(usize, Range<usize>)> {
+    let (found, complete) = walk_json(content, path);
+    if !complete {
+        return None;
+    }
+    let (start, value) = found.last().copied().unwrap_or_else(|| {
+        let start = skip_whitespace(content.as_bytes(), 0);
+        (start, start)
+    });
+    Some((start, value..skip_value(content.as_bytes(), value)))
+}
+
+/// The start of every member along `path` and the start of its value, and
+/// whether the whole path was found.
+fn walk_json(content: &str, path: &str) -> (Vec<(usize, usize)>, bool) {
+    let bytes = content.as_bytes();
+    let mut position = skip_whitespace(bytes, 0);
+    let mut found = Vec::new();
+    for segment in segments(path) {
+        let member = match (segment, bytes.get(position)) {
+            (Segment::Key(key), Some(b'{')) => find_member(bytes, position, |index, name| {
+                (json_key(content, index, name) == Some(key)).then_some(index)
+            }),
+            (Segment::Index(wanted), Some(b'[')) => {
+                let mut count = 0;
+                find_member(bytes, position, |index, _| {
+                    count += 1;
+                    (count - 1 == wanted).then_some(index)
+                })
+            }
+            _ => None,
+        };
+        let Some((start, value)) = member else {
+            return (found, false);
+        };
+        found.push((start, value));
+        position = value;
+    }
+    (found, true)
+}
+
+/// The decoded key of the object member starting at `start`, if `name` is
+/// the position of its string.
+fn json_key(content: &str, start: usize, name: Option<usize>) -> Option<&str> {
+    let end = name?;
+    content.get(start + 1..end - 1)
+}
+
+/// Walks the members of the object or array at `open`. `matches` is called
+/// with the start of every member (and, for objects, the end of its key)
+/// and decides which one is wanted. Returns its start and the start of its
+/// value.
+fn find_member(
+    bytes: &[u8],
+    open: usize,
+    mut matches: impl FnMut(usize, Option<usize>) -> Option<usize>,
+) -> Option<(usize, usize)> {
+    let is_object = bytes[open] == b'{';
+    let mut position = skip_whitespace(bytes, open + 1);
+    while position < bytes.len() && bytes[position] != b'}' && bytes[position] != b']' {
+        let start = position;
+        let (key_end, value) = if is_object {
+            let key_end = skip_value(bytes, start);
+            let colon = skip_whitespace(bytes, key_end);
+            (Some(key_end), skip_whitespace(bytes, colon + 1))
+        } else {
+            (None, start)
+        };
+        if let Some(found) = matches(start, key_end) {
+            return Some((found, value));
+        }
+        position = skip_whitespace(bytes, skip_value(bytes, value));
+        if bytes.get(position) == Some(&b',') {
+            position = skip_whitespace(bytes, position + 1);
+        }
+    }
+    None
+}
+
+pub fn skip_whitespace(bytes: &[u8], mut position: usize) -> usize {
+    while position < bytes.len() && bytes[position].is_ascii_whitespace() {
+        position += 1;
+    }
+    position
+}
+
+/// Position just after the JSON value starting at `position`.
+pub fn skip_value(bytes: &[u8], mut position: usize) -> usize {
+    match bytes.get(position) {
+        Some(b'"') => {
+            position += 1;
+            while position < bytes.len() && bytes[position] != b'"' {
+                position += if bytes[position] == b'\\' { 2 } else { 1 };
+            }
+            position + 1
+        }
+        Some(b'{') | Some(b'[') => {
+            let mut depth = 0;
+            while position < bytes.len() {
+                match bytes[position] {
+                    b'"' => {
+                        position = skip_value(bytes, position);
+                        continue;
+                    }
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return position + 1;
+                        }
+                    }
+                    _ => {}
+                }
+                position += 1;
+            }
+            position
+        }
+        _ => {
+            while position < bytes.len() && !b",}] \t\r\n".contains(&bytes[position]) {
+                position += 1;
+            }
+            position
+        }
+    }
+}
+
+fn locate_toml(content: &str, path: &str) -> Option<usize> {
+    use toml_edit::{Array, ArrayOfTables, ImDocument, InlineTable, Item, Table, Value};
+    enum Node<'a> {
+        Table(&'a Table),
+        InlineTable(&'a InlineTable),
+        Array(&'a Array),
+        ArrayOfTables(&'a ArrayOfTables),
+        Leaf,
+    }
+    fn from_value(value: &Value) -> Node<'_> {
+        match value {
+            Value::InlineTable(table) => Node::InlineTable(table),
+            Value::Array(array) => Node::Array(array),
+            _ => Node::Leaf,
+        }
+    }
+    fn from_item(item: &Item) -> Node<'_> {
+        match item {
+            Item::Table(table) => Node::Table(table),
+            Item::ArrayOfTables(tables) => Node::ArrayOfTables(tables),
+            Item::Value(value) => from_value(value),
+            Item::None => Node::Leaf,
+        }
+    }
+    let document = ImDocument::parse(content).ok()?;
+    let mut node = Node::Table(document.as_table());
+    let mut found = None;
+    for segment in segments(path) {
+        let step = match (segment, node) {
+            (Segment::Key(key), Node::Table(table)) => table.get(key).map(|item| {
+                (table.key(key).and_then(|key| key.span()).or_else(|| item.span()), from_item(item))
+            }),
+            (Segment::Key(key), Node::InlineTable(table)) => table
+                .get(key)
+                .map(|value| (table.key(key).and_then(|key| key.span()), from_value(value))),
+            (Segment::Index(index), Node::ArrayOfTables(tables)) => {
+                tables.get(index).map(|table| (table.span(), Node::Table(table)))
+            }
+            (Segment::Index(index), Node::Array(array)) => {
+                array.get(index).map(|value| (value.span(), from_value(value)))
+            }
+            _ => None,
+        };
+        let Some((span, next)) = step else {
+            break;
+        };
+        if let Some(span) = span {
+            found = Some(span.start);
+        }
+        node = next;
+    }
+    found.or(Some(0))
+}
+
+/// YAML is located by indentation alone, which is enough for the block
+/// style config files are written in. Flow collections (`[a, b]`) are
+/// located as a whole.
+fn locate_yaml(content: &str, path: &str) -> Option<usize> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        let text = line.trim_end();
+        let indent = text.len() - text.trim_start().len();
+        // the column of the first key, after any `- ` list markers
+        let mut key = indent;
+        while text[key..].starts_with("- ") || &text[key..] == "-" {
+            key += 1;
+            key += text[key..].len() - text[key..].trim_start().len();
+        }
+        if !text.trim_start().is_empty() && !text.trim_start().starts_with('#') {
+            lines.push((offset, text, indent, key));
+        }
+        offset += line.len();
+    }
+    // a key's value ends at the next line that is not indented further,
+    // except for a list written at the same indentation as its key
+    let key_end = |start: usize, column: usize| {
+        (start + 1..lines.len())
+            .find(|&line| lines[line].2 < column || (lines[line].2 == column && lines[line].3 == column))
+            .unwrap_or(lines.len())
+    };
+    let item_end = |start: usize, column: usize| {
+        (start + 1..lines.len()).find(|&line| lines[line].2 <= column).unwrap_or(lines.len())
+    };
+    let mut range = 0..lines.len();
+    // the column nested values must start at or after
+    let mut column = 0;
+    let mut found = None;
+    for segment in segments(path) {
+        let step = match segment {
+            Segment::Key(key) => {
+                let key_column = range.clone().map(|line| lines[line].3).filter(|&key| key >= column).min();
+                range.clone().find(|&line| {
+                    let (_, text, _, start) = lines[line];
+                    Some(start) == key_column && {
+                        let rest = text[start..].trim_start_matches(['"', '\'']);
+                        rest.starts_with(key) && rest[key.len()..].trim_start_matches(['"', '\'']).starts_with(':')
+                    }
+                }).map(|line| {
+                    let start = lines[line].3;
+                    (line, start, key_end(line, start))
+                })
+            }
+            Segment::Index(index) => {
+                let dash_column = range
+                    .clone()
+                    .filter(|&line| lines[line].3 > lines[line].2 && lines[line].2 >= column)
+                    .map(|line| lines[line].2)
+                    .min();
+                range
+                    .clone()
+                    .filter(|&line| Some(lines[line].2) == dash_column && lines[line].3 > lines[line].2)
+                    .nth(index)
+                    .map(|line| {
+                        let start = lines[line].2;
+                        (line, start, item_end(line, start))
+                    })
+            }
+        };
+        let Some((line, start, end)) = step else {
+            break;
+        };
+        found = Some(lines[line].0 + start);
+        range = line..end;
+        column = start + 1;
+    }
+    found.or(Some(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(content: &str, format: FileFormat, path: &str) -> (usize, usize) {
+        line_column(content, locate(content, format, path).unwrap())
+    }
+
+    #[test]
+    fn json_keys_and_items() {
+        let content = r#"{
+  // the look
+  "theme": { "width": 2 },
+  "modules": [
+    { "name": "CPU" },
+    { "name": "Disks", "stats": ["name", "speed"] }
+  ]
+}"#;
+        assert_eq!(at(content, FileFormat::Jsonc, "theme.width"), (3, 14));
+        assert_eq!(at(content, FileFormat::Jsonc, "modules[1]"), (6, 5));
+        assert_eq!(at(content, FileFormat::Jsonc, "modules[1].stats[1]"), (6, 42));
+        // what cannot be found is reported at its closest enclosing value
+        assert_eq!(at(content, FileFormat::Jsonc, "theme.align"), (3, 3));
+        assert_eq!(at(content, FileFormat::Jsonc, "modules[5].name"), (4, 3));
+    }
+
+    #[test]
+    fn toml_keys_and_items() {
+        let content = "# the look\n[theme]\nwidth = 2\n\n[[modules]]\nname = \"CPU\"\n\n[[modules]]\nname = \"Disks\"\nstats = [\"name\", \"speed\"]\n";
+        assert_eq!(at(content, FileFormat::Toml, "theme.width"), (3, 1));
+        assert_eq!(at(content, FileFormat::Toml, "modules[1].name"), (9, 1));
+        assert_eq!(at(content, FileFormat::Toml, "modules[1].stats[1]"), (10, 18));
+        assert_eq!(at(content, FileFormat::Toml, "theme.align"), (2, 2));
+    }
+
+    #[test]
+    fn yaml_keys_and_items() {
+        let content = "# the look\ntheme:\n  width: 2\nmodules:\n  - name: CPU\n  - name: Disks\n    stats:\n      - name\n      - speed\n";
+        assert_eq!(at(content, FileFormat::Yaml, "theme.width"), (3, 3));
+        assert_eq!(at(content, FileFormat::Yaml, "modules[1]"), (6, 3));
+        assert_eq!(at(content, FileFormat::Yaml, "modules[1].name"), (6, 5));
+        assert_eq!(at(content, FileFormat::Yaml, "modules[1].stats[1]"), (9, 7));
+        assert_eq!(at(content, FileFormat::Yaml, "theme.align"), (2, 1));
+    }
+}
@@ -0,0 +1,179 @@
+//! Upgrades config files written for older versions of the schema.
+//!
+//! Every config file carries the schema `version` it was written for. When
+//! a file is older than [`CURRENT_VERSION`] the migrations below are applied
+//! one after another, each describing what it changed so the user can be
+//! told. A file without a `version` is assumed to be current, which keeps
+//! small project or system files that only set a couple of keys valid.
+use crate::config::ConfigError;
+use serde_json::{Map, Value};
+
+pub const CURRENT_VERSION: &str = "0.2.0";
+
+struct Migration {
+    from: &'static str,
+    to: &'static str,
+    apply: fn(&mut Map<String, Value>, &mut Vec<String>),
+}
+
+const MIGRATIONS: &[Migration] = &[Migration {
+    from: "0.1.0",
+    to: "0.2.0",
+    apply: |config, changes| {
+        if let Some(Value::Object(theme)) = config.get_mut("theme") {
+            move_key(theme, "headers", "text", "theme", changes);
+            move_key(theme, "groups", "text", "theme", changes);
+        }
+        rename_stat(config, "CPU", "speed", "frequency", changes);
+    },
+}];
+
+/// Brings `value` up to [`CURRENT_VERSION`] and returns a description of
+/// every change. Returns no changes when the file is already current.
+pub fn migrate(value: &mut Value) -> Result<Vec<String>, ConfigError> {
+    let Value::Object(config) = value else {
+        return Ok(Vec::new());
+    };
+    let mut version = match config.get("version") {
+        None => return Ok(Vec::new()),
+        Some(Value::String(version)) => version.clone(),
+        Some(other) => return Err(version_error(format!("expected a version string like \"{}\", found {}", CURRENT_VERSION, other))),
+    };
+    if parse_version(&version).is_none() {
+        return Err(version_error(format!("`{}` is not a version like \"{}\"", version, CURRENT_VERSION)));
+    }
+    if parse_version(&version) > parse_version(CURRENT_VERSION) {
+        return Err(version_error(format!(
+            "the config was written for version {} but this ff only understands up to {}",
+            version, CURRENT_VERSION
+        )));
+    }
+    let mut changes = Vec::new();
+    while version != CURRENT_VERSION {
+        let migration = MIGRATIONS
+            .iter()
+            .find(|migration| migration.from == version)
+            .ok_or_else(|| version_error(format!("no upgrade is known from config version {}", version)))?;
+        (migration.apply)(config, &mut changes);
+        changes.push(format!("updated `version` from {} to {}", migration.from, migration.to));
+        version = migration.to.to_string();
+    }
+    config.insert("version".to_string(), Value::from(version));
+    Ok(changes)
+}
+
+fn version_error(message: String) -> ConfigError {
+    ConfigError {
+        path: "version".to_string(),
+        message,
+    }
+}
+
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let mut parts = version.split('.').map(|part| part.parse::<u64>().ok());
+    let version = (parts.next()??, parts.next()??, parts.next()??);
+    parts.next().is_none().then_some(version)
+}
+
+/// Moves `parent.key` into `parent.into.key`, unless the new location is
+/// already set, in which case the old key is dropped.
+fn move_key(parent: &mut Map<String, Value>, key: &str, into: &str, path: &str, changes: &mut Vec<String>) {
+    let Some(value) = parent.remove(key) else {
+        return;
+    };
+    let target = parent
+        .entry(into.to_string())
+        .or_insert_with(|| Value::Object(Map::new()));
+    let Value::Object(target) = target else {
+        return;
+    };
+    if target.contains_key(key) {
+        changes.push(format!("removed `{0}.{1}` because `{0}.{2}.{1}` is already set", path, key, into));
+    } else {
+        target.insert(key.to_string(), value);
+        changes.push(format!("moved `{0}.{1}` to `{0}.{2}.{1}`", path, key, into));
+    }
+}
+
+fn rename_stat(config: &mut Map<String, Value>, module: &str, from: &str, to: &str, changes: &mut Vec<String>) {
+    let Some(Value::Array(modules)) = config.get_mut("modules") else {
+        return;
+    };
+    for (index, entry) in modules.iter_mut().enumerate() {
+        if entry.get("name").and_then(Value::as_str) != Some(module) {
+            continue;
+        }
+        let Some(Value::Array(stats)) = entry.get_mut("stats") else {
+            continue;
+        };
+        for stat in stats.iter_mut().filter(|stat| stat.as_str() == Some(from)) {
+            *stat = Value::from(to);
+            changes.push(format!("renamed {} stat `{}` to `{}` in `modules[{}]`", module, from, to, index));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::edit::rewrite;
+    use crate::config::FileFormat;
+    use serde_json::json;
+
+    #[test]
+    fn upgrades_old_files() {
+        let mut value = json!({
+            "version": "0.1.0",
+            "theme": { "headers": "#ff0000", "text": { "groups": "#00ff00" }, "groups": "#0000ff" },
+            "modules": [{ "name": "CPU", "stats": ["name", "speed"] }, { "name": "Memory", "stats": ["speed"] }]
+        });
+        let changes = migrate(&mut value).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "version": CURRENT_VERSION,
+                "theme": { "text": { "groups": "#00ff00", "headers": "#ff0000" } },
+                "modules": [{ "name": "CPU", "stats": ["name", "frequency"] }, { "name": "Memory", "stats": ["speed"] }]
+            })
+        );
+        assert_eq!(
+            changes,
+            [
+                "moved `theme.headers` to `theme.text.headers`",
+                "removed `theme.groups` because `theme.text.groups` is already set",
+                "renamed CPU stat `speed` to `frequency` in `modules[0]`",
+                "updated `version` from 0.1.0 to 0.2.0",
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_current_and_unversioned_files() {
+        for value in [json!({ "version": CURRENT_VERSION, "theme": { "headers": "#ff0000" } }), json!({ "timeout": 100 })] {
+            let mut migrated = value.clone();
+            assert!(migrate(&mut migrated).unwrap().is_empty());
+            assert_eq!(migrated, value);
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_versions() {
+        for version in [json!("9.0.0"), json!("0.1"), json!(1), json!("0.0.1")] {
+            let error = migrate(&mut json!({ "version": version })).unwrap_err();
+            assert_eq!(error.path, "version");
+        }
+    }
+
+    #[test]
+    fn upgraded_files_keep_their_comments() {
+        let content = "# mine\nversion = \"0.1.0\"\n\n[theme]\nheaders = \"#ff0000\"\nwidth = 0.8 # most of it\n";
+        let before = FileFormat::Toml.parse_str(content).unwrap();
+        let mut after = before.clone();
+        migrate(&mut after).unwrap();
+        let upgraded = rewrite(content, FileFormat::Toml, &before, &after).unwrap();
+        assert!(upgraded.contains("# mine\nversion = \"0.2.0\""));
+        assert!(upgraded.contains("width = 0.8 # most of it"));
+        assert_eq!(FileFormat::Toml.parse_str(&upgraded).unwrap(), after);
+        assert_eq!(rewrite("version: 0.1.0\n", FileFormat::Yaml, &before, &after), None);
+    }
+}
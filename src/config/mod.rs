@@ -1,166 +1,228 @@
-use serde_json::json;
+pub mod args;
+pub mod check;
+pub mod edit;
+pub mod editor;
+pub mod environment;
+pub mod formats;
+pub mod layers;
+pub mod locate;
+pub mod migrate;
+pub mod profiles;
+mod model;
+pub use args::{ArgHandler, Args};
+pub use editor::open_config;
+pub use formats::FileFormat;
+pub use layers::{Layer, Origin, Origins};
+pub use model::*;
+use crate::error::Error;
 use std::{
-    collections::HashMap, env, fs, path::Path
+    fmt, fs, io, path::{Path, PathBuf}
 };
-pub fn open_config() -> () {
-    let localappdata = env::var("LOCALAPPDATA").unwrap();
-    let config_path = Path::new(&localappdata).join("ninju_fastfestch");
-    let config_file = config_path.join("config.json");
-    // open conig file in default editor
-    if config_file.exists() {
-        #[cfg(target_os = "windows")]
-        {
-            std::process::Command::new("cmd")
-                .args(&["/C", "start", "", config_file.to_str().unwrap()])
-                .spawn()
-                .expect("failed to open config file");
-        }
-        #[cfg(target_os = "macos")]
-        {
-            std::process::Command::new("open")
-                .arg(config_file)
-                .spawn()
-                .expect("failed to open config file");
-        }
-        #[cfg(target_os = "linux")]
-        {
-            std::process::Command::new("xdg-open")
-                .arg(config_file)
-                .spawn()
-                .expect("failed to open config file");
-        }
+fn config_io(path: &Path) -> impl FnOnce(io::Error) -> Error + '_ {
+    move |source| Error::ConfigIo {
+        path: path.to_path_buf(),
+        source,
     }
 }
-pub fn import_config() -> serde_json::Value {
-    let mut localappdata: String;
-    #[cfg(target_os="windows")]
-    {
-        localappdata = env::var("LOCALAPPDATA").unwrap();
+
+/// The user's own config file, which `--config` opens and which is created
+/// with the defaults if it does not exist.
+pub fn user_config_path(config_file: Option<&Path>) -> Result<PathBuf, Error> {
+    match layers::explicit_config_path(config_file) {
+        Some(path) => Ok(path),
+        None => match layers::user_config_file()? {
+            Some(path) => Ok(path),
+            None => Ok(layers::user_config_dir()?.join(layers::CONFIG_FILE)),
+        },
     }
-    #[cfg(not(target_os="windows"))]
-    {
-        localappdata = env::var("HOME").unwrap();
+}
+
+/// Where a previous version of a config file is kept: next to it, with
+/// `suffix` appended. Upgrades keep the file as it was before in
+/// [`UPGRADE_BACKUP`], `--config` keeps the last valid version in
+/// [`EDIT_BACKUP`], so that editing never replaces the pre-upgrade file.
+pub fn backup_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(suffix);
+    PathBuf::from(backup)
+}
+
+pub const UPGRADE_BACKUP: &str = ".bak";
+pub const EDIT_BACKUP: &str = ".edit.bak";
+
+/// The effective config together with the origin of each of its values.
+pub struct Loaded {
+    pub config: Config,
+    pub value: serde_json::Value,
+    pub origins: Origins,
+    /// Things the user should know about, e.g. files that were upgraded.
+    pub notices: Vec<String>,
+}
+
+impl Loaded {
+    /// Every effective value with the file (or other layer) that set it.
+    pub fn describe_origins(&self) -> Vec<(String, String, String)> {
+        layers::leaves(&self.value)
+            .into_iter()
+            .map(|(path, value)| {
+                let origin = self.origins.get(&path).map_or_else(String::new, |origin| origin.to_string());
+                (path, value.to_string(), origin)
+            })
+            .collect()
     }
-    let config_path = Path::new(&localappdata).join("ninju_fastfestch");
-    if !config_path.exists() {
-        fs::create_dir_all(&config_path).unwrap();
+}
+
+/// Loads every config layer (see [`layers`]), including `profile` and the
+/// environment, and merges them with `cli`, the values given on the
+/// command line. The user file is created
+/// with the defaults if neither it nor an explicit file exists.
+pub fn import_config(config_file: Option<&Path>, profile: Option<&str>, cli: serde_json::Value) -> Result<Loaded, Error> {
+    let (value, origins, notices) = merge_config(config_file, profile, cli)?;
+    let config = parse_config(value.clone()).map_err(|source| Error::ConfigParse {
+        path: origins
+            .get(&source.path)
+            .and_then(Origin::path)
+            .map_or_else(|| PathBuf::from("config"), Path::to_path_buf),
+        source,
+    })?;
+    Ok(Loaded { config, value, origins, notices })
+}
+
+/// The layers [`import_config`] reads, merged but not checked against the
+/// [`Config`] schema, for the commands that edit a config which may be
+/// invalid.
+pub fn merge_config(
+    config_file: Option<&Path>,
+    profile: Option<&str>,
+    cli: serde_json::Value,
+) -> Result<(serde_json::Value, Origins, Vec<String>), Error> {
+    let mut notices = Vec::new();
+    let mut sources = vec![Layer {
+        origin: Origin::Default,
+        value: serde_json::to_value(Config::default()).expect("the default config always serializes"),
+    }];
+    if let Some(path) = layers::system_config_path() {
+        if let Some(value) = read_layer(&path, &mut notices)? {
+            sources.push(Layer { origin: Origin::System(path), value });
+        }
     }
-    let config_file = config_path.join("config.json");
-    if !config_file.exists() {
-        let default_config = json!({
-            "version": "0.1.0",
-            "theme": {
-                "gradient": {
-                    "from": 0xffffff,
-                    "to": 0x000000
-                },
-                "headers": 0x9FA1C9,
-                "text": {"key":0xF5C2E7,"value":0x75AFFA},
-                "width": 0.5,
-                "align": "left"
-            },
-            "modules": [
-                {
-                    "name": "CPU",
-                    "icon": "f4bc",
-                    "stats": [
-                        "name",
-                        "cores",
-                        "threads",
-                        "speed",
-                        "usage",
-                        "vendor",
-                        "architecture"
-                    ]
-                },
-                {
-                    "icon": "efc5",
-                    "name": "Memory",
-                    "stats": [
-                        "total",
-                        "used",
-                        "free",
-                        "available",
-                        "swap_total",
-                        "swap_used",
-                        "swap_free",
-                        "percent"
-                    ]
+    let user_file = match layers::explicit_config_path(config_file) {
+        Some(path) => {
+            let value = read_layer(&path, &mut notices)?.ok_or_else(|| Error::ConfigIo {
+                path: path.clone(),
+                source: io::Error::new(io::ErrorKind::NotFound, "config file does not exist"),
+            })?;
+            Some((path, value))
+        }
+        None => {
+            let config_path = layers::user_config_dir()?;
+            if !config_path.exists() {
+                fs::create_dir_all(&config_path).map_err(config_io(&config_path))?;
+            }
+            let config_file = match layers::user_config_file()? {
+                Some(config_file) => config_file,
+                None => {
+                    let config_file = config_path.join(layers::CONFIG_FILE);
+                    fs::write(&config_file, default_config(FileFormat::Json))
+                        .map_err(config_io(&config_file))?;
+                    config_file
                 }
-            ]
+            };
+            read_layer(&config_file, &mut notices)?.map(|value| (config_file, value))
+        }
+    };
+    if let Some((path, value)) = user_file {
+        sources.push(Layer { origin: Origin::User(path), value });
+    }
+    if let Some(path) = layers::project_config_path() {
+        if let Some(value) = read_layer(&path, &mut notices)? {
+            sources.push(Layer { origin: Origin::Project(path), value });
+        }
+    }
+    let (files, origins) = layers::merge(&sources);
+    if let Some(profile) = profiles::selected(profile) {
+        sources.extend(profiles::layers(&files, &origins, &profile)?);
+    }
+    let (below, _) = layers::merge(&sources);
+    sources.extend(environment::layers(&below, &mut notices)?);
+    sources.push(Layer { origin: Origin::CommandLine, value: cli });
+
+    let (value, origins) = layers::merge(&sources);
+    Ok((value, origins, notices))
+}
+
+/// Reads and parses a single layer, `None` if the file does not exist.
+/// Files written for an older schema are upgraded (see [`migrate`]) and
+/// saved in place with the edits `ff config set` makes, keeping the
+/// original next to them as `<file>.bak`. Files that cannot be edited that
+/// way, such as YAML files, are left alone and upgraded for the run only.
+fn read_layer(path: &Path, notices: &mut Vec<String>) -> Result<Option<serde_json::Value>, Error> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(source) => return Err(config_io(path)(source)),
+    };
+    let format = FileFormat::from_path(path);
+    let parse_error = |source| Error::ConfigParse {
+        path: path.to_path_buf(),
+        source,
+    };
+    let mut value = format.parse_str(&content).map_err(parse_error)?;
+    let original = value.clone();
+    let changes = migrate::migrate(&mut value).map_err(parse_error)?;
+    if !changes.is_empty() {
+        let backup = backup_path(path, UPGRADE_BACKUP);
+        let saved = edit::rewrite(&content, format, &original, &value)
+            .map(|upgraded| fs::write(&backup, &content).and_then(|_| fs::write(path, upgraded)));
+        notices.push(match saved {
+            None => format!(
+                "{} uses an older config version and is upgraded for this run only, rewriting it would lose its comments; make these changes to it:",
+                path.display()
+            ),
+            Some(Ok(())) => format!(
+                "upgraded {} to config version {} (previous file saved as {}):",
+                path.display(),
+                migrate::CURRENT_VERSION,
+                backup.display()
+            ),
+            Some(Err(err)) => format!(
+                "{} uses an older config version and could not be upgraded in place ({}); using the upgraded values for this run:",
+                path.display(),
+                err
+            ),
         });
-        fs::write(&config_file, default_config.to_string()).unwrap();
+        notices.extend(changes.into_iter().map(|change| format!("  {}", change)));
     }
-    let config_content = fs::read_to_string(&config_file).unwrap();
-    serde_json::from_str(&config_content).unwrap()
+    Ok(Some(value))
 }
 
-pub struct ArgHandler {
-    pub args: Vec<String>,
+/// The built-in defaults written as a config file in `format`.
+pub fn default_config(format: FileFormat) -> String {
+    let value = serde_json::to_value(Config::default()).expect("the default config always serializes");
+    formats::render(&value, format)
 }
-#[derive(Debug, PartialEq, Eq, Hash)]
-pub enum Args {
-    Help,
-    Version,
-    Config,
-    Width,
-    Align,
-    Invalid
+
+/// Deserializes the merged config into a [`Config`], reporting the path of
+/// the first field that failed to parse.
+pub fn parse_config(value: serde_json::Value) -> Result<Config, ConfigError> {
+    serde_path_to_error::deserialize(value).map_err(|err| ConfigError {
+        path: err.path().to_string(),
+        message: err.into_inner().to_string(),
+    })
 }
 
-impl ArgHandler {
-    pub fn new() -> ArgHandler {
-        ArgHandler {
-            args: env::args().collect(),
-        }
-    }
-    pub fn handle(&self) -> Vec<HashMap<Args, String>> {
-        let mut args = Vec::new();
-        for arg in self.args.iter().skip(1) {
-            let argname = arg.split('=').collect::<Vec<&str>>()[0];
-            args.push(match argname {
-                "-h" | "--help" => {
-                    let mut map = HashMap::new();
-                    map.insert(Args::Help, "".to_string());
-                    map
-                }
-                "-v" | "--version" => {
-                    let mut map = HashMap::new();
-                    map.insert(Args::Version, "".to_string());
-                    map
-                }
-                "-c" | "--config" => {
-                    let mut map = HashMap::new();
-                    map.insert(Args::Config, "".to_string());
-                    map
-                }
-                "-w" | "--width" => {
-                    if !arg.contains('=') {
-                        let mut map = HashMap::new();
-                        map.insert(Args::Help, "".to_string());
-                        return vec![map];
-                    }
-                    let mut map = HashMap::new();
-                    map.insert(Args::Width, arg.split('=').collect::<Vec<&str>>()[1].to_string());
-                    map
-                }
-                "-a" | "--align" => {
-                    if !arg.contains('=') {
-                        let mut map = HashMap::new();
-                        map.insert(Args::Help, "".to_string());
-                        return vec![map];
-                    }
-                    let mut map = HashMap::new();
-                    map.insert(Args::Align, arg.split('=').collect::<Vec<&str>>()[1].to_string());
-                    map
-                }
-                _ => {
-                    let mut map = HashMap::new();
-                    map.insert(Args::Invalid, arg.to_string());
-                    map
-                }
-            })
+#[derive(Debug)]
+pub struct ConfigError {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.path == "." {
+            write!(f, "invalid config: {}", self.message)
+        } else {
+            write!(f, "invalid config at `{}`: {}", self.path, self.message)
         }
-        args
     }
 }
\ No newline at end of file
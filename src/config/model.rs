@@ -0,0 +1,325 @@
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use crate::config::migrate::CURRENT_VERSION;
+use crate::modules;
+use crate::suggest::did_you_mean;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Root of `config.json`. Every field has a default so a partial file is
+/// filled in from [`Config::default`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub version: String,
+    pub theme: Theme,
+    /// Default time in milliseconds to wait for each module.
+    pub timeout: u64,
+    /// What to do with stats a module does not know, e.g. a misspelt name.
+    pub unknown_stats: UnknownStats,
+    pub modules: Vec<ModuleConfig>,
+    /// Named sets of the settings above, see [`profiles`](super::profiles).
+    /// They are applied while the config is loaded and kept as written.
+    pub profiles: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    pub gradient: Gradient,
+    pub text: TextColors,
+    #[serde(deserialize_with = "deserialize_width")]
+    pub width: f64,
+    pub align: Align,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Gradient {
+    pub from: Color,
+    pub to: Color,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TextColors {
+    pub headers: Color,
+    /// Colour of the sub-headers of modules with several groups, e.g. one
+    /// per disk.
+    pub groups: Color,
+    pub key: Color,
+    pub value: Color,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Align {
+    #[default]
+    Left,
+    Right,
+    Center,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModuleConfig {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<Icon>,
+    #[serde(default)]
+    pub stats: Vec<String>,
+    /// Executable implementing the module, see `modules::plugin` for the
+    /// protocol. Built-in modules leave this unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plugin: Option<PathBuf>,
+    /// What to do when the module cannot be initialized on this machine.
+    #[serde(default)]
+    pub unavailable: Unavailable,
+    /// Overrides the global `timeout` for this module, in milliseconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Unavailable {
+    /// Leave the module out of the output.
+    #[default]
+    Hide,
+    /// Render the module header followed by "not available".
+    Show,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnknownStats {
+    /// Leave the row out; a warning names the stat either way.
+    #[default]
+    Hide,
+    /// Render the row with "unknown" as its value.
+    Show,
+}
+
+/// A 24 bit RGB colour. Accepts either an integer (`0x9FA1C9` written as
+/// a JSON number) or a hex string such as `"#9FA1C9"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color(pub u32);
+
+/// A glyph given as its hexadecimal code point, e.g. `"f4bc"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Icon(pub char);
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            version: CURRENT_VERSION.to_string(),
+            theme: Theme::default(),
+            timeout: 1000,
+            unknown_stats: UnknownStats::Hide,
+            modules: vec![
+                ModuleConfig {
+                    name: "CPU".to_string(),
+                    icon: Some(Icon('\u{f4bc}')),
+                    stats: ["name", "cores", "threads", "frequency", "usage", "vendor", "architecture"]
+                        .iter()
+                        .map(|stat| stat.to_string())
+                        .collect(),
+                    plugin: None,
+                    unavailable: Unavailable::Hide,
+                    timeout: None,
+                },
+                ModuleConfig {
+                    name: "Memory".to_string(),
+                    icon: Some(Icon('\u{efc5}')),
+                    stats: [
+                        "total", "used", "free", "available", "swap_total", "swap_used", "swap_free",
+                        "percent",
+                    ]
+                    .iter()
+                    .map(|stat| stat.to_string())
+                    .collect(),
+                    plugin: None,
+                    unavailable: Unavailable::Hide,
+                    timeout: None,
+                },
+            ],
+            profiles: serde_json::Map::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn module_timeout(&self, module: &ModuleConfig) -> Duration {
+        Duration::from_millis(module.timeout.unwrap_or(self.timeout))
+    }
+    /// Keeps only the modules called `names`, in that order. Built-in
+    /// modules that are not configured are added with all of their stats.
+    pub fn select_modules(&mut self, names: &[String]) -> Result<(), String> {
+        let mut selected = Vec::new();
+        for name in names {
+            let module = match self.modules.iter().find(|module| &module.name == name) {
+                Some(module) => module.clone(),
+                None => match modules::find(name) {
+                    Some(info) => ModuleConfig {
+                        name: info.name.to_string(),
+                        icon: None,
+                        stats: info.stat_names().map(String::from).collect(),
+                        plugin: None,
+                        unavailable: Unavailable::Hide,
+                        timeout: None,
+                    },
+                    None => {
+                        let known = self.modules.iter().map(|module| module.name.as_str());
+                        let known = known.chain(modules::REGISTRY.iter().map(|info| info.name));
+                        return Err(format!("unknown module `{}`{}", name, did_you_mean(name, known)));
+                    }
+                },
+            };
+            selected.push(module);
+        }
+        self.modules = selected;
+        Ok(())
+    }
+    /// Replaces the stats of the configured module called `name`. Stats of
+    /// built-in modules are checked against the ones they support.
+    pub fn override_stats(&mut self, name: &str, stats: Vec<String>) -> Result<(), String> {
+        let Some(index) = self.modules.iter().position(|module| module.name == name) else {
+            let known = self.modules.iter().map(|module| module.name.as_str());
+            return Err(format!(
+                "module `{}` is not shown{}",
+                name,
+                match did_you_mean(name, known) {
+                    hint if hint.is_empty() => ", add it with --modules".to_string(),
+                    hint => hint,
+                }
+            ));
+        };
+        let module = &mut self.modules[index];
+        if let (None, Some(info)) = (&module.plugin, modules::find(name)) {
+            if let Some(stat) = stats.iter().find(|stat| !info.has_stat(stat)) {
+                return Err(format!(
+                    "unknown stat `{}` for module `{}`{}",
+                    stat,
+                    name,
+                    did_you_mean(stat, info.stat_names())
+                ));
+            }
+        }
+        module.stats = stats;
+        Ok(())
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme {
+            gradient: Gradient::default(),
+            text: TextColors::default(),
+            width: 0.5,
+            align: Align::Left,
+        }
+    }
+}
+
+impl Default for Gradient {
+    fn default() -> Gradient {
+        Gradient {
+            from: Color(0xffffff),
+            to: Color(0x000000),
+        }
+    }
+}
+
+impl Default for TextColors {
+    fn default() -> TextColors {
+        TextColors {
+            headers: Color(0x9FA1C9),
+            groups: Color(0xCBA6F7),
+            key: Color(0xF5C2E7),
+            value: Color(0x75AFFA),
+        }
+    }
+}
+
+impl Align {
+    pub fn parse(align: &str) -> Option<Align> {
+        match align {
+            "left" => Some(Align::Left),
+            "right" => Some(Align::Right),
+            "center" => Some(Align::Center),
+            _ => None,
+        }
+    }
+}
+
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("#{:06X}", self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        struct ColorVisitor;
+        impl de::Visitor<'_> for ColorVisitor {
+            type Value = Color;
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an RGB colour between 0x000000 and 0xFFFFFF or a string like \"#9FA1C9\"")
+            }
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<Color, E> {
+                if value > 0xffffff {
+                    return Err(E::invalid_value(de::Unexpected::Unsigned(value), &self));
+                }
+                Ok(Color(value as u32))
+            }
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<Color, E> {
+                if value < 0 {
+                    return Err(E::invalid_value(de::Unexpected::Signed(value), &self));
+                }
+                self.visit_u64(value as u64)
+            }
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Color, E> {
+                let hex = value
+                    .strip_prefix('#')
+                    .or_else(|| value.strip_prefix("0x"))
+                    .unwrap_or(value);
+                match u32::from_str_radix(hex, 16) {
+                    Ok(color) if hex.len() == 6 => Ok(Color(color)),
+                    _ => Err(E::invalid_value(de::Unexpected::Str(value), &self)),
+                }
+            }
+        }
+        deserializer.deserialize_any(ColorVisitor)
+    }
+}
+
+impl Serialize for Icon {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:x}", self.0 as u32))
+    }
+}
+
+impl<'de> Deserialize<'de> for Icon {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Icon, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        u32::from_str_radix(&hex, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .map(Icon)
+            .ok_or_else(|| {
+                de::Error::invalid_value(de::Unexpected::Str(&hex), &"a hexadecimal unicode code point like \"f4bc\"")
+            })
+    }
+}
+
+pub(crate) fn deserialize_width<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    let width = f64::deserialize(deserializer)?;
+    if width > 0.0 && width <= 1.0 {
+        Ok(width)
+    } else {
+        Err(de::Error::invalid_value(
+            de::Unexpected::Float(width),
+            &"a fraction of the terminal width between 0.0 (exclusive) and 1.0",
+        ))
+    }
+}
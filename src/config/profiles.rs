@@ -0,0 +1,150 @@
+//! Named profiles, e.g. a short login banner next to a detailed hardware
+//! report, selected with `--profile NAME` or `FF_PROFILE=NAME`.
+//!
+//! A profile may set `theme`, `timeout`, `unknown_stats` and `modules` and
+//! inherits everything else from the config files, or from the profile it
+//! `extends`. It is merged like a config layer: tables key by key,
+//! everything else, including `modules`, as a whole. Profiles come after
+//! the files and before environment variables and command line flags, so
+//! those still override them.
+use crate::config::{ConfigError, Layer, Origin, Origins};
+use crate::error::Error;
+use crate::suggest::did_you_mean;
+use serde_json::Value;
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// The environment variable that selects a profile.
+pub const VARIABLE: &str = "FF_PROFILE";
+
+/// The profile given on the command line, else in the environment.
+pub fn selected(profile: Option<&str>) -> Option<String> {
+    profile
+        .map(String::from)
+        .or_else(|| env::var(VARIABLE).ok().filter(|profile| !profile.is_empty()))
+}
+
+/// Layers for profile `name` and every profile it extends, the one it
+/// inherits from first. `files` is the merged config files and `origins`
+/// says which file set each of its values.
+pub fn layers(files: &Value, origins: &Origins, name: &str) -> Result<Vec<Layer>, Error> {
+    let empty = serde_json::Map::new();
+    let profiles = files.get("profiles").and_then(Value::as_object).unwrap_or(&empty);
+    let mut chain: Vec<&str> = Vec::new();
+    let mut next = Some(name);
+    while let Some(name) = next {
+        let Some(profile) = profiles.get(name) else {
+            let message = match profiles.is_empty() {
+                true => format!("unknown profile `{}`, the config defines no profiles", name),
+                false => format!("unknown profile `{}`{}", name, did_you_mean(name, profiles.keys().map(String::as_str))),
+            };
+            return Err(match chain.last() {
+                Some(extending) => invalid(origins, format!("profiles.{}.extends", extending), message),
+                None => Error::Usage(message),
+            });
+        };
+        if chain.contains(&name) {
+            let path = format!("profiles.{}.extends", chain.last().expect("a profile extends this one"));
+            let message = format!("profile `{}` extends itself: `{}` -> `{}`", name, chain.join("` -> `"), name);
+            return Err(invalid(origins, path, message));
+        }
+        chain.push(name);
+        next = profile.get("extends").and_then(Value::as_str);
+    }
+    Ok(chain
+        .into_iter()
+        .rev()
+        .map(|name| {
+            let mut value = profiles[name].clone();
+            if let Value::Object(settings) = &mut value {
+                settings.remove("extends");
+            }
+            Layer {
+                origin: Origin::Profile(name.to_string()),
+                value,
+            }
+        })
+        .collect())
+}
+
+fn invalid(origins: &Origins, path: String, message: String) -> Error {
+    Error::ConfigParse {
+        path: origins
+            .get(&path)
+            .and_then(Origin::path)
+            .map_or_else(|| PathBuf::from("config"), Path::to_path_buf),
+        source: ConfigError { path, message },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::layers::merge;
+    use serde_json::json;
+
+    fn files() -> Value {
+        json!({
+            "theme": { "width": 0.5, "align": "left" },
+            "modules": [{ "name": "CPU" }, { "name": "Memory" }],
+            "profiles": {
+                "short": { "modules": [{ "name": "CPU" }], "theme": { "width": 0.3 } },
+                "wide": { "extends": "short", "theme": { "width": 1.0 } },
+                "loop": { "extends": "around" },
+                "around": { "extends": "loop" }
+            }
+        })
+    }
+
+    /// The files with profile `name` applied, as `import_config` merges them.
+    fn with_profile(name: Option<&str>) -> Result<Value, String> {
+        let files = Layer { origin: Origin::Default, value: files() };
+        let (value, origins) = merge(std::slice::from_ref(&files));
+        let mut sources = vec![files];
+        if let Some(name) = selected(name) {
+            sources.extend(layers(&value, &origins, &name).map_err(|err| err.to_string())?);
+        }
+        Ok(merge(&sources).0)
+    }
+
+    #[test]
+    fn without_a_profile_the_files_apply() {
+        if env::var_os(VARIABLE).is_none() {
+            assert_eq!(with_profile(None).unwrap(), files());
+        }
+        assert_eq!(selected(Some("short")).as_deref(), Some("short"));
+    }
+
+    #[test]
+    fn profiles_override_the_files() {
+        let value = with_profile(Some("short")).unwrap();
+        assert_eq!(value["theme"], json!({ "width": 0.3, "align": "left" }));
+        assert_eq!(value["modules"], json!([{ "name": "CPU" }]));
+    }
+
+    #[test]
+    fn extended_profiles_apply_first() {
+        let value = with_profile(Some("wide")).unwrap();
+        assert_eq!(value["theme"], json!({ "width": 1.0, "align": "left" }));
+        assert_eq!(value["modules"], json!([{ "name": "CPU" }]));
+        let (files, origins) = merge(&[Layer { origin: Origin::Default, value: files() }]);
+        let names: Vec<String> = layers(&files, &origins, "wide")
+            .unwrap()
+            .into_iter()
+            .map(|layer| layer.origin.to_string())
+            .collect();
+        assert_eq!(names, ["profile short", "profile wide"]);
+    }
+
+    #[test]
+    fn unknown_and_circular_profiles_are_errors() {
+        assert_eq!(with_profile(Some("shrot")), Err("unknown profile `shrot`, did you mean `short`?".to_string()));
+        let err = with_profile(Some("loop")).unwrap_err();
+        assert!(err.ends_with("profile `loop` extends itself: `loop` -> `around` -> `loop`"), "{}", err);
+        let (files, origins) = merge(&[Layer { origin: Origin::Default, value: json!({}) }]);
+        assert_eq!(
+            layers(&files, &origins, "short").map(|_| ()).unwrap_err().to_string(),
+            "unknown profile `short`, the config defines no profiles"
+        );
+    }
+}
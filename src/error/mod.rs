@@ -0,0 +1,111 @@
+//! Errors that end the program.
+//!
+//! Every fallible path returns [`Error`] up to `main`, which prints it with
+//! [`Chalk`](crate::chalk::Chalk) and exits with [`Error::exit_code`]:
+//!
+//! | code | meaning                                          |
+//! |------|--------------------------------------------------|
+//! | 0    | success                                          |
+//! | 2    | invalid command line arguments                   |
+//! | 3    | the config file could not be found, read, written or opened in an editor |
+//! | 4    | the config file or an `FF_` environment variable is not valid, or `ff config check` found problems |
+//! | 5    | the terminal could not be queried                |
+//! | 6    | a module failed while collecting its stats       |
+//! | 7    | the output could not be written                  |
+//! | 8    | `ff serve` could not listen on its address       |
+//! | 9    | `--push` could not send the stats                |
+use crate::config::ConfigError;
+use std::{fmt, io, path::PathBuf};
+
+/// Every exit code and what it means, for the help and the man page.
+pub const EXIT_CODES: &[(i32, &str)] = &[
+    (0, "success"),
+    (2, "invalid command line arguments"),
+    (3, "the config file could not be found, read, written or opened in an editor"),
+    (4, "the config file or an FF_ environment variable is not valid, or `ff config check` found problems"),
+    (5, "the terminal could not be queried"),
+    (6, "a module failed while collecting its stats"),
+    (7, "the output could not be written"),
+    (8, "`ff serve` could not listen on its address"),
+    (9, "`--push` could not send the stats"),
+];
+
+#[derive(Debug)]
+pub enum Error {
+    Usage(String),
+    ConfigIo { path: PathBuf, source: io::Error },
+    ConfigParse { path: PathBuf, source: ConfigError },
+    /// `ff config check` found problems, which it has already printed.
+    ConfigCheck { problems: usize },
+    /// An `FF_` environment variable holds an invalid config value.
+    Environment { variable: String, message: String },
+    /// The editor for `--config` could not be started or failed.
+    Editor { command: String, source: io::Error },
+    Terminal(io::Error),
+    /// Modules failed while collecting their stats, which has already been
+    /// reported for each of them. The stats of the others were printed.
+    ModulesFailed { failed: usize },
+    Render(io::Error),
+    /// `ff serve` could not listen on `address`.
+    Serve { address: String, source: io::Error },
+    /// The stats could not be sent to the `--push` target.
+    Push { target: String, source: io::Error },
+}
+
+impl Error {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Usage(_) => 2,
+            Error::ConfigIo { .. } | Error::Editor { .. } => 3,
+            Error::ConfigParse { .. } | Error::ConfigCheck { .. } | Error::Environment { .. } => 4,
+            Error::Terminal(_) => 5,
+            Error::ModulesFailed { .. } => 6,
+            Error::Render(_) => 7,
+            Error::Serve { .. } => 8,
+            Error::Push { .. } => 9,
+        }
+    }
+    /// A short suggestion printed below the error message, if there is one.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            Error::Usage(_) => Some("To get help use ff -h"),
+            Error::ConfigParse { .. } => Some("Fix the value in the config file or delete it to regenerate the defaults."),
+            Error::Environment { .. } => Some("Fix the value of the environment variable or unset it."),
+            Error::Editor { .. } => Some("Set the VISUAL or EDITOR environment variable to the editor you want to use."),
+            Error::Terminal(_) => Some("Set the COLUMNS environment variable when stdout is not a terminal."),
+            Error::Serve { .. } => Some("Choose a free address and port with --listen."),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Usage(message) => f.write_str(message),
+            Error::ConfigIo { path, source } => write!(f, "cannot access `{}`: {}", path.display(), source),
+            Error::ConfigParse { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::ConfigCheck { problems: 1 } => f.write_str("found 1 problem in the config"),
+            Error::ConfigCheck { problems } => write!(f, "found {} problems in the config", problems),
+            Error::Environment { variable, message } => write!(f, "{}: {}", variable, message),
+            Error::Editor { command, source } => write!(f, "cannot edit the config with {}: {}", command, source),
+            Error::Terminal(err) => write!(f, "cannot determine the terminal width: {}", err),
+            Error::ModulesFailed { failed: 1 } => f.write_str("1 module failed"),
+            Error::ModulesFailed { failed } => write!(f, "{} modules failed", failed),
+            Error::Render(err) => write!(f, "cannot write output: {}", err),
+            Error::Serve { address, source } => write!(f, "cannot listen on {}: {}", address, source),
+            Error::Push { target, source } => write!(f, "cannot send the stats to {}: {}", target, source),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::ConfigIo { source, .. } | Error::Editor { source, .. } | Error::Serve { source, .. }
+            | Error::Push { source, .. } => Some(source),
+            Error::Terminal(err) | Error::Render(err) => Some(err),
+            _ => None,
+        }
+    }
+}
@@ -1,4 +1,5 @@
 #![allow(dead_code)]
+use crate::error::Error;
 use regex::Regex;
 use crossterm::terminal::size as terminal_size;
 use unicode_width::UnicodeWidthStr;
@@ -6,7 +7,7 @@ pub struct Format {}
 impl Format {
     pub fn center(width: u32, text: &str) -> String {
         let text_len = Format::strlen_no_color(text);
-        let padding = (width - text_len) / 2;
+        let padding = width.saturating_sub(text_len) / 2;
         let mut centered_text = String::new();
         Format::pad(&mut centered_text, padding);
         centered_text.push_str(text);
@@ -17,13 +18,13 @@ impl Format {
         let len1 = Format::strlen_no_color(str1); let len2 = Format::strlen_no_color(str2);
         let mut stretched_text = String::new();
         stretched_text.push_str(str1);
-        Format::pad(&mut stretched_text, width - len1 - len2);
+        Format::pad(&mut stretched_text, width.saturating_sub(len1 + len2));
         stretched_text.push_str(str2);
         stretched_text
     }
     pub fn right(width: u32, text: &str) -> String {
         let text_len = Format::strlen_no_color(text);
-        let padding = width - text_len;
+        let padding = width.saturating_sub(text_len);
         let mut right_text = String::new();
         Format::pad(&mut right_text, padding);
         right_text.push_str(text);
@@ -31,17 +32,26 @@ impl Format {
     }
     pub fn left(width: u32, text: &str) -> String {
         let text_len = Format::strlen_no_color(text);
-        let padding = width - text_len;
+        let padding = width.saturating_sub(text_len);
         let mut left_text = String::new();
         left_text.push_str(text);
         Format::pad(&mut left_text, padding);
         left_text
     }
-    pub fn console_width() -> u32 {
-        terminal_size().unwrap().0 as u32
+    /// Width of the attached terminal, falling back to `$COLUMNS` when
+    /// stdout is not a terminal.
+    pub fn console_width() -> Result<u32, Error> {
+        terminal_size()
+            .map(|(columns, _)| columns as u32)
+            .or_else(|err| {
+                std::env::var("COLUMNS")
+                    .ok()
+                    .and_then(|columns| columns.parse().ok())
+                    .ok_or(Error::Terminal(err))
+            })
     }
     fn strlen_no_color(text: &str) -> u32 {
-        let re = Regex::new(r"\x1b\[[\d;]*m").unwrap();
+        let re = Regex::new(r"\x1b\[[\d;]*m").expect("the colour code pattern is valid");
         let text = re.replace_all(text, "");
         text.width() as u32
     }
@@ -0,0 +1,241 @@
+//! `ff --help` and `ff manpage`.
+//!
+//! Both are rendered from the same [`document`], which is built from the
+//! flag and command tables, the module registry and the exit codes, so the
+//! help and the man page always describe what the binary actually does.
+use crate::chalk::Chalk;
+use crate::config::args::{COMMANDS, FLAGS};
+use crate::config::layers::{APP_DIR, CONFIG_FILE};
+use crate::config::TextColors;
+use crate::error::EXIT_CODES;
+use crate::modules::REGISTRY;
+
+pub struct Section {
+    pub title: &'static str,
+    /// Text shown before the entries, may be empty.
+    pub text: String,
+    pub entries: Vec<Entry>,
+}
+
+pub struct Entry {
+    pub term: String,
+    /// One or more lines.
+    pub description: String,
+    /// Empty if there is none. Code is written in backticks.
+    pub example: String,
+    /// Nested entries, e.g. the stats of a module.
+    pub entries: Vec<Entry>,
+}
+
+impl Entry {
+    fn new(term: impl Into<String>, description: impl Into<String>, example: impl Into<String>) -> Entry {
+        Entry {
+            term: term.into(),
+            description: description.into(),
+            example: example.into(),
+            entries: Vec::new(),
+        }
+    }
+}
+
+const SUMMARY: &str = "show system information in the terminal";
+
+const DESCRIPTION: &str = "ff prints stats about this machine, such as its processor, memory, batteries and disks, \
+sized and aligned to the terminal. Which modules and stats are shown and how they look is set in the configuration files.";
+
+/// Every section of the help, in the order it is shown.
+pub fn document() -> Vec<Section> {
+    vec![
+        Section {
+            title: "FLAGS",
+            text: String::new(),
+            entries: FLAGS
+                .iter()
+                .map(|flag| Entry::new(flag.usage(), flag.description, flag.example))
+                .collect(),
+        },
+        Section {
+            title: "COMMANDS",
+            text: String::new(),
+            entries: COMMANDS
+                .iter()
+                .map(|command| Entry::new(command.usage(), command.description, command.example))
+                .collect(),
+        },
+        Section {
+            title: "MODULES",
+            text: "Modules are listed under `modules` in the configuration, each with the `stats` it shows, in order.\n\
+                Plugins add modules of their own, see `plugin` in the configuration."
+                .to_string(),
+            entries: REGISTRY
+                .iter()
+                .map(|info| Entry {
+                    entries: info
+                        .stats
+                        .iter()
+                        .map(|stat| {
+                            let term = match stat.unit {
+                                "" => stat.name.to_string(),
+                                unit => format!("{} ({})", stat.name, unit),
+                            };
+                            Entry::new(term, stat.description, format!("`{}`", stat.example))
+                        })
+                        .collect(),
+                    ..Entry::new(info.name, info.description, "")
+                })
+                .collect(),
+        },
+        Section {
+            title: "FILES",
+            text: "Configuration files are merged in this order, each overriding the ones before it.\n\
+                Environment variables override the files and command line flags override them all.\n\
+                Every file may also be written as .jsonc, .toml, .yaml or .yml."
+                .to_string(),
+            entries: vec![
+                Entry::new(
+                    format!("/etc/{}/{}", APP_DIR, CONFIG_FILE),
+                    format!("System wide configuration, %ProgramData%\\{} on Windows.", APP_DIR),
+                    "",
+                ),
+                Entry::new(
+                    format!("$XDG_CONFIG_HOME/{}/{}", APP_DIR, CONFIG_FILE),
+                    format!(
+                        "The user's configuration, created with the defaults on the first run.\n\
+                        ~/.config/{0} when XDG_CONFIG_HOME is unset, %LOCALAPPDATA%\\{0} on Windows.\n\
+                        --config-file or FF_CONFIG read a different file instead.",
+                        APP_DIR
+                    ),
+                    "",
+                ),
+                Entry::new(".ff.json", "Project configuration, read from the current directory.", ""),
+            ],
+        },
+        Section {
+            title: "ENVIRONMENT",
+            text: String::new(),
+            entries: vec![
+                Entry::new("FF_CONFIG", "Path of the user configuration file, like --config-file.", ""),
+                Entry::new(
+                    "FF_<KEY>",
+                    "Overrides a configuration key, named after its path in capitals with _ between the parts.\n\
+                    Values are read like `ff config set` reads them.",
+                    "`FF_THEME_WIDTH=0.8` or `FF_UNKNOWN_STATS=show`",
+                ),
+                Entry::new("FF_PROFILE", "The profile to use, like --profile.", ""),
+                Entry::new("FF_MODULES", "The modules to show, in this order, like --modules.", "`FF_MODULES=CPU,Disks`"),
+                Entry::new(
+                    "FF_MODULE_<NAME>_<KEY>",
+                    "Overrides a setting of one configured module.",
+                    "`FF_MODULE_CPU_STATS=name,usage`",
+                ),
+                Entry::new("XDG_CONFIG_HOME", "Directory of the user configuration.", ""),
+                Entry::new("VISUAL, EDITOR", "Editor used by --config, tried in this order.", ""),
+                Entry::new("COLUMNS", "Width of the output when it is not written to a terminal.", ""),
+            ],
+        },
+        Section {
+            title: "EXIT STATUS",
+            text: String::new(),
+            entries: EXIT_CODES
+                .iter()
+                .map(|(code, meaning)| Entry::new(code.to_string(), *meaning, ""))
+                .collect(),
+        },
+    ]
+}
+
+/// The help as shown by `ff --help`, in the colours of the theme.
+pub fn terminal(colors: &TextColors) -> String {
+    let mut string = format!("ff - {}\n\n", SUMMARY);
+    for section in document() {
+        string.push_str(&format!("{}\n", Chalk::colorize(section.title, colors.headers.0, true)));
+        for line in section.text.lines() {
+            string.push_str(&format!("   {}\n", line));
+        }
+        if !section.text.is_empty() {
+            string.push('\n');
+        }
+        for entry in &section.entries {
+            terminal_entry(&mut string, entry, 3, colors);
+        }
+    }
+    string
+}
+
+fn terminal_entry(string: &mut String, entry: &Entry, indent: usize, colors: &TextColors) {
+    let pad = " ".repeat(indent);
+    let color = if indent == 3 { colors.headers.0 } else { colors.groups.0 };
+    string.push_str(&format!("{}{}\n", pad, Chalk::colorize(&entry.term, color, indent == 3)));
+    for line in entry.description.lines() {
+        string.push_str(&format!("{}   {}\n", pad, Chalk::colorize(line, colors.key.0, false)));
+    }
+    if !entry.example.is_empty() {
+        let example = format!("e.g. {}", entry.example);
+        string.push_str(&format!("{}   {}\n", pad, Chalk::colorize(&example, colors.value.0, false)));
+    }
+    for nested in &entry.entries {
+        terminal_entry(string, nested, indent + 3, colors);
+    }
+    if indent == 3 {
+        string.push('\n');
+    }
+}
+
+/// The man page, in roff as read by `man`.
+pub fn manpage() -> String {
+    let mut page = format!(".TH FF 1 \"\" \"ff {}\" \"User Commands\"\n", env!("CARGO_PKG_VERSION"));
+    page.push_str(&format!(".SH NAME\nff \\- {}\n", roff(SUMMARY)));
+    page.push_str(".SH SYNOPSIS\n.B ff\n[\\fIFLAGS\\fR] [\\fICOMMAND\\fR]\n");
+    page.push_str(&format!(".SH DESCRIPTION\n{}\n", roff(DESCRIPTION)));
+    for section in document() {
+        page.push_str(&format!(".SH {}\n", section.title));
+        if !section.text.is_empty() {
+            page.push_str(&format!("{}\n", roff_lines(&section.text)));
+        }
+        for entry in &section.entries {
+            man_entry(&mut page, entry);
+        }
+    }
+    page
+}
+
+fn man_entry(page: &mut String, entry: &Entry) {
+    page.push_str(&format!(".TP\n\\fB{}\\fR\n{}\n", roff(&entry.term), roff_lines(&entry.description)));
+    if !entry.example.is_empty() {
+        page.push_str(&format!(".br\ne.g. {}\n", roff(&entry.example)));
+    }
+    if !entry.entries.is_empty() {
+        page.push_str(".RS\n");
+        for nested in &entry.entries {
+            man_entry(page, nested);
+        }
+        page.push_str(".RE\n");
+    }
+}
+
+/// Lines of text with explicit line breaks between them.
+fn roff_lines(text: &str) -> String {
+    text.lines().map(roff).collect::<Vec<_>>().join("\n.br\n")
+}
+
+/// Escapes `text` for roff and prints `code` in bold.
+fn roff(text: &str) -> String {
+    let mut escaped = String::new();
+    let mut code = false;
+    for char in text.chars() {
+        match char {
+            '\\' => escaped.push_str("\\e"),
+            '-' => escaped.push_str("\\-"),
+            '`' => {
+                escaped.push_str(if code { "\\fR" } else { "\\fB" });
+                code = !code;
+            }
+            char => escaped.push(char),
+        }
+    }
+    // a leading dot or quote would start a request
+    if escaped.starts_with(['.', '\'']) {
+        escaped.insert_str(0, "\\&");
+    }
+    escaped
+}
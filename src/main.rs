@@ -1,194 +1,472 @@
 mod chalk;
+mod completions;
 mod config;
+mod error;
 mod format;
+mod help;
 mod modules;
+mod output;
+mod serve;
+mod suggest;
 use chalk::Chalk;
+use config::{Align, Config, Unavailable, UnknownStats};
+use error::Error;
 use format::Format;
-use lazy_static::lazy_static;
-use modules::cpu::Cpu;
-use modules::disks::Disk;
-use modules::memory::Memory;
-use modules::battery::Battery;
-use modules::module_trait::Module;
-use serde_json;
-use std::collections::HashMap;
-use std::sync::RwLock;
+use modules::Collected;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
 
-lazy_static! {
-    static ref CONFIG: RwLock<serde_json::Value> = RwLock::new(config::import_config());
-    static ref HELP: serde_json::Value = serde_json::json!({
-        "width": {
-            "args": ["-w", "--width"],
-            "description": "Change the width of the output in the terminal.\nThe Format is a float:\n0.0 - 1.0 (0% - 100%)",
-            "example": "`-w=0.5` or `--width=0.5`"
-        },
-        "align": {
-            "args": ["-a", "--align"],
-            "description": "Change the alignment of the output in the terminal.\nThe Format is a string:\nleft, right, center",
-            "example": "`-a=left` or `--align=left`"
-        },
-        "config": {
-            "args": ["-c", "--config"],
-            "description": "Change the configuration of the program.\nThis will open the configuration file in your default text editor.",
-            "example": "`-c` or `--config`"
-        },
-        "version": {
-            "args": ["-v", "--version"],
-            "description": "Print the version of the program to the terminal.",
-            "example": "`-v` or `--version`"
-        }
-    });
-}
-
-fn display_help() -> String {
-    let mut string = String::new();
-    for (key, value) in HELP.as_object().unwrap() {
-        string.push_str(&format!(
-            "{}\n",
-            &Chalk::colorize(
-                &format!(
-                    "{} [ {} ]",
-                    &key,
-                    value["args"]
-                        .as_array()
-                        .unwrap()
-                        .iter()
-                        .filter_map(|arg| arg.as_str())
-                        .collect::<Vec<&str>>()
-                        .join(" | ")
-                ),
-                CONFIG.read().unwrap()["theme"]["headers"].as_u64().unwrap() as u32,
-                true,
-            ),
-        ));
-        for line in value["description"].as_str().unwrap().lines() {
-            string.push_str(&format!(
-                "   {}\n",
-                &Chalk::colorize(
-                    line,
-                    CONFIG.read().unwrap()["theme"]["text"]["key"].as_u64().unwrap() as u32,
-                    false,
-                ),
-            ));
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("{} {}", Chalk::colorize("error:", 0xF38BA8, true), err);
+        if let Some(hint) = err.hint() {
+            eprintln!("{}", Chalk::colorize(hint, 0x9FA1C9, false));
         }
-        string.push_str("\n");
+        std::process::exit(err.exit_code());
     }
-    string
-
 }
 
-fn main() {
-    let mut modules: HashMap<String, Box<dyn Module>> = HashMap::new();
-    modules.insert("CPU".to_string(), Box::new(Cpu::new()));
-    modules.insert("Memory".to_string(), Box::new(Memory::new()));
-    modules.insert("Battery".to_string(), Box::new(Battery::new()));
-    modules.insert("Disks".to_string(), Box::new(Disk::new()));
-    let mut data: Vec<Vec<HashMap<String, String>>> = Vec::new();
-    //modules.insert("battery".to_string(), Box::new(Battery::new())); // Assuming Battery::new() exists
-
+fn run() -> Result<(), Error> {
     let arghandler = config::ArgHandler::new();
-    let args = arghandler.handle();
-    if args.len() > 0 {
-        for arg in args {
-            if arg.contains_key(&config::Args::Help) {
-                let string = display_help();
-                println!("{}", string);
-                return;
+    if let Some((current, words)) = arghandler.completion_request() {
+        return print_lines(completions::complete(words, current).into_iter().map(|(candidate, description)| {
+            match description.is_empty() {
+                true => candidate,
+                false => format!("{}\t{}", candidate, description),
+            }
+        }));
+    }
+    let args = arghandler.handle()?;
+    let mut action = None;
+    let mut operand = String::new();
+    let mut edit_value = None;
+    let mut modules = None;
+    let mut profile = None;
+    let mut output_format = output::OutputFormat::Text;
+    let mut listen = serve::DEFAULT_ADDRESS.to_string();
+    let mut cache = serve::DEFAULT_CACHE;
+    let mut push = None;
+    let mut stats = Vec::new();
+    let mut config_file = None;
+    let mut cli = serde_json::json!({});
+    for (arg, value) in args {
+        match arg {
+            config::Args::Width => {
+                let width: f64 = value.parse().expect("the argument parser only accepts widths that are numbers");
+                cli["theme"]["width"] = serde_json::Value::from(width);
+            }
+            config::Args::Align => cli["theme"]["align"] = serde_json::Value::from(value),
+            config::Args::DefaultConfig => {
+                // printed before any config is loaded, so that the output
+                // can become the user's config file
+                let format = config::FileFormat::parse(&value).expect("the argument parser only accepts known file formats");
+                return print_lines(config::default_config(format).lines());
+            }
+            config::Args::ConfigFile => config_file = Some(PathBuf::from(value)),
+            config::Args::Modules => modules = Some(value),
+            config::Args::Profile => profile = Some(value),
+            config::Args::Format => {
+                output_format = output::OutputFormat::parse(&value).expect("the argument parser only accepts known output formats");
+            }
+            config::Args::Listen => listen = value,
+            config::Args::Cache => {
+                cache = value.parse().expect("the argument parser only accepts cache durations that are whole numbers");
+            }
+            config::Args::Push => push = Some(value),
+            config::Args::Stats => stats.push(value),
+            config::Args::Value => edit_value = Some(value),
+            arg if action.is_none() => {
+                action = Some(arg);
+                operand = value;
+            }
+            _ => {}
+        }
+    }
+    // both work on the files themselves, so they must not fail when the
+    // config is invalid
+    if action == Some(config::Args::Check) {
+        return check_config(config_file.as_deref());
+    }
+    if action == Some(config::Args::Config) {
+        return config::open_config(config_file.as_deref(), |report| {
+            let _ = print_lines(report_lines(report).iter());
+        });
+    }
+    // in the default colours, so that they work whatever state the config
+    // is in
+    if action == Some(config::Args::Help) {
+        return print_lines(help::terminal(&Config::default().theme.text).lines());
+    }
+    if action == Some(config::Args::Version) {
+        return print_lines([format!("version {}", env!("CARGO_PKG_VERSION"))].iter());
+    }
+    if action == Some(config::Args::Manpage) {
+        return print_lines(help::manpage().lines());
+    }
+    if action == Some(config::Args::Completions) {
+        let shell = completions::Shell::parse(&operand).ok_or_else(|| {
+            let hint = match suggest::did_you_mean(&operand, completions::SHELLS.iter().copied()) {
+                hint if hint.is_empty() => format!(", expected one of {}", completions::SHELLS.join(", ")),
+                hint => hint,
+            };
+            Error::Usage(format!("unknown shell `{}`{}", operand, hint))
+        })?;
+        return print_lines(shell.script().lines());
+    }
+    if matches!(
+        action,
+        Some(config::Args::Get | config::Args::Set | config::Args::Unset | config::Args::AddModule)
+    ) {
+        // not checked against the schema, so that an invalid config can be
+        // repaired; edit_file refuses edits that make it worse
+        let (value, _, notices) = config::merge_config(config_file.as_deref(), profile.as_deref(), cli)?;
+        for notice in &notices {
+            eprintln!("{} {}", Chalk::colorize("note:", 0x9FA1C9, true), notice);
+        }
+        if action == Some(config::Args::Get) {
+            let value = config::edit::lookup(&value, &operand)
+                .ok_or_else(|| Error::Usage(format!("`{}` is not set.", operand)))?;
+            let text = match value {
+                serde_json::Value::String(text) => text.clone(),
+                value => serde_json::to_string_pretty(value).expect("config values always serialize"),
+            };
+            return print_lines(text.lines());
+        }
+        let path = config::user_config_path(config_file.as_deref())?;
+        let (edit, done) = match action {
+            Some(config::Args::Set) => {
+                let value = config::edit::parse_value(edit_value.as_deref().unwrap_or_default());
+                let done = format!("set `{}` to {} in {}", operand, value, path.display());
+                (config::edit::Edit::Set(operand, value), done)
             }
-            else if arg.contains_key(&config::Args::Version) {
-                println!("version {}", CONFIG.read().unwrap()["version"].as_str().unwrap());
-                return;
+            Some(config::Args::Unset) => {
+                let done = format!("removed `{}` from {}", operand, path.display());
+                (config::edit::Edit::Unset(operand), done)
             }
-            else if arg.contains_key(&config::Args::Config) {
-                config::open_config();
-                return;
+            _ => {
+                let stats: Vec<String> = match stats.first() {
+                    Some(list) => split_list(list),
+                    None => modules::find(&operand)
+                        .map(|info| info.stat_names().map(String::from).collect())
+                        .unwrap_or_default(),
+                };
+                let done = format!("added module `{}` to {}", operand, path.display());
+                let module = serde_json::json!({ "name": operand, "stats": stats });
+                (config::edit::Edit::Append("modules".to_string(), module), done)
             }
-            else if arg.contains_key(&config::Args::Width) {
-                let width = arg.get(&config::Args::Width).unwrap();
-                if let Ok(width) = width.parse::<f64>() {
-                    if width > 0.0 && width <= 1.0 {
-                        CONFIG.write().unwrap()["theme"]["width"] = serde_json::Value::from(width);
+        };
+        config::edit::edit_file(&path, edit, &value)?;
+        return print_lines([done].iter());
+    }
+    let loaded = config::import_config(config_file.as_deref(), profile.as_deref(), cli)?;
+    for notice in &loaded.notices {
+        eprintln!("{} {}", Chalk::colorize("note:", 0x9FA1C9, true), notice);
+    }
+    let mut config = loaded.config.clone();
+    if let Some(names) = &modules {
+        config.select_modules(&split_list(names)).map_err(Error::Usage)?;
+    }
+    if action != Some(config::Args::AddModule) {
+        for stats in &stats {
+            let (module, stats) = stats.split_once('=').ok_or_else(|| {
+                Error::Usage(format!("invalid value `{}` for `--stats`: expected MODULE=stat,stat, e.g. CPU=name,usage", stats))
+            })?;
+            config.override_stats(module, split_list(stats)).map_err(Error::Usage)?;
+        }
+    }
+    let config = &config;
+    match action {
+        Some(config::Args::ListModules) => return list_modules(config),
+        Some(config::Args::ListStats) => return list_stats(config, &operand),
+        Some(config::Args::ShowOrigin) => {
+            return print_lines(loaded.describe_origins().into_iter().map(|(path, value, origin)| {
+                format!(
+                    "{} = {}  {}",
+                    Chalk::colorize(&path, config.theme.text.key.0, false),
+                    Chalk::colorize(&value, config.theme.text.value.0, false),
+                    Chalk::colorize(&format!("({})", origin), config.theme.text.groups.0, false),
+                )
+            }));
+        }
+        _ => {}
+    }
+    let mut headers = Vec::new();
+    let mut jobs = Vec::new();
+    let mut warned = Vec::new();
+    let mut unknown = Vec::new();
+    for module in &config.modules {
+        let mut stats = module.stats.clone();
+        let source = match (&module.plugin, modules::find(&module.name)) {
+            (Some(path), _) => modules::Source::Plugin(path.clone()),
+            (None, Some(info)) => {
+                for stat in module.stats.iter().filter(|stat| !info.has_stat(stat)) {
+                    // once per run, even if the stat or module is listed twice
+                    if warned.contains(&(&module.name, stat)) {
+                        continue;
                     }
+                    warned.push((&module.name, stat));
+                    warn(&format!(
+                        "module `{}` has no stat `{}`{}",
+                        module.name,
+                        stat,
+                        suggest::did_you_mean(stat, info.stat_names())
+                    ));
                 }
-            }
-            else if arg.contains_key(&config::Args::Align) {
-                let align = arg.get(&config::Args::Align).unwrap().as_str();
-                if align == "left" || align == "right" || align == "center" {
-                    CONFIG.write().unwrap()["theme"]["align"] = serde_json::Value::from(align);
+                if config.unknown_stats == UnknownStats::Hide {
+                    stats.retain(|stat| info.has_stat(stat));
+                }
+                if output_format.labelled() || push.is_some() || action == Some(config::Args::Serve) {
+                    for label in info.labels.iter().filter(|label| info.has_stat(label)) {
+                        if !stats.iter().any(|stat| stat == label) {
+                            stats.push(label.to_string());
+                        }
+                    }
                 }
+                modules::Source::Builtin(info)
             }
-            else {
-                println!("\x1b[1;31m{} is not a valid argument.\x1b[0m\nTo get help use ff -h", arg.get(&config::Args::Invalid).unwrap());
-                return;                
+            (None, None) => {
+                if !unknown.contains(&&module.name) {
+                    unknown.push(&module.name);
+                    warn(&format!(
+                        "unknown module `{}`{}",
+                        module.name,
+                        suggest::did_you_mean(&module.name, modules::REGISTRY.iter().map(|info| info.name))
+                    ));
+                }
+                continue;
             }
+        };
+        jobs.push(modules::Job {
+            name: module.name.clone(),
+            source,
+            stats,
+            timeout: config.module_timeout(module),
+        });
+        headers.push((
+            module,
+            match module.icon {
+                Some(icon) => format!("{}  {}", icon.0, module.name),
+                None => module.name.clone(),
+            },
+        ));
+    }
+    if action == Some(config::Args::Serve) {
+        return serve::run(&listen, Duration::from_millis(cache), || {
+            let collected = modules::collect_all(jobs.clone());
+            output::Report::new(jobs.iter().zip(&collected))
+        });
+    }
+    let collected = modules::collect_all(jobs.clone());
+    let mut failed = 0;
+    for ((module, _), collected) in headers.iter().zip(&collected) {
+        if let Collected::Failed(err) = collected {
+            warn(&format!("module `{}`: {}", module.name, err));
+            failed += 1;
         }
     }
-    for module in CONFIG.read().unwrap()["modules"].as_array().unwrap() {
-        if let Some(module_name) = module["name"].as_str() {
-            if let Some(module_instance) = modules.get_mut(module_name) {
-                data.push(
-                    module_instance
-                        .handle(module.as_object().unwrap()["stats"].as_array().unwrap()),
-                );
-            }
+    let report = output::Report::new(jobs.iter().zip(&collected));
+    if let Some(url) = &push {
+        output::statsd::push(&report, url)?;
+        return failures(failed);
+    }
+    if let Some(output) = output::render(&report, output_format) {
+        // not line by line, CSV rows end in CRLF
+        print_lines(std::iter::once(output.strip_suffix('\n').unwrap_or(&output)))?;
+        return failures(failed);
+    }
+    let mut data: Vec<(String, Collected)> = Vec::new();
+    for ((module, header), collected) in headers.into_iter().zip(collected) {
+        let policy = module.unavailable;
+        if matches!(collected, Collected::Unavailable(_)) && policy == Unavailable::Hide {
+            continue;
         }
+        data.push((header, collected));
     }
+    let console_width = Format::console_width()?;
     let mut str = String::new();
-    for (index, module) in data.iter().enumerate() {
+    let width = (console_width as f64 * config.theme.width) as u32;
+    for (header, module) in &data {
         str.push_str(&format!(
             "{}\n",
             &Format::center(
-                (Format::console_width() as f64 * CONFIG.read().unwrap()["theme"]["width"].as_f64().unwrap())
-                    as u32,
-                &Chalk::colorize(
-                    &format!(
-                        "{}  {}",
-                        char::from_u32(
-                            u32::from_str_radix(
-                                CONFIG.read().unwrap()["modules"][index]["icon"].as_str().unwrap(),
-                                16
-                            )
-                            .unwrap()
-                        )
-                        .unwrap(),
-                        &CONFIG.read().unwrap()["modules"][index]["name"].as_str().unwrap(),
-                    ),
-                    CONFIG.read().unwrap()["theme"]["headers"].as_u64().unwrap() as u32,
-                    true,
-                ),
+                width,
+                &Chalk::colorize(header, config.theme.text.headers.0, true),
             )
         ));
-        for stat in module {
-            for (key, value) in stat {
-                str.push_str(&format!(
-                    "{}\n",
-                    &Format::stretch(
-                        (Format::console_width() as f64
-                            * CONFIG.read().unwrap()["theme"]["width"].as_f64().unwrap())
-                            as u32,
-                        &Chalk::colorize(
-                            &key,
-                            CONFIG.read().unwrap()["theme"]["text"]["key"].as_u64().unwrap() as u32,
-                            false,
-                        ),
-                        &Chalk::colorize(
-                            &value,
-                            CONFIG.read().unwrap()["theme"]["text"]["value"].as_u64().unwrap() as u32,
-                            false,
-                        ),
-                    )
-                ));
+        let marker = match module {
+            Collected::Stats(groups) => {
+                for group in groups {
+                    if let Some(title) = &group.title {
+                        str.push_str(&format!(
+                            "{}\n",
+                            &Format::center(
+                                width,
+                                &Chalk::colorize(title, config.theme.text.groups.0, false),
+                            )
+                        ));
+                    }
+                    for stat in &group.stats {
+                        str.push_str(&format!(
+                            "{}\n",
+                            &Format::stretch(
+                                width,
+                                &Chalk::colorize(&stat.name, config.theme.text.key.0, false),
+                                &Chalk::colorize(&stat.value.to_string(), config.theme.text.value.0, false),
+                            )
+                        ));
+                    }
+                }
+                continue;
             }
+            Collected::Unavailable(_) => "not available".to_string(),
+            Collected::Failed(_) => "failed".to_string(),
+            Collected::TimedOut(timeout) => format!("timed out after {} ms", timeout.as_millis()),
+        };
+        str.push_str(&format!(
+            "{}\n",
+            &Format::center(
+                width,
+                &Chalk::colorize(&marker, config.theme.text.value.0, false),
+            )
+        ));
+    }
+    let lines = str.lines().map(|str| match config.theme.align {
+        Align::Center => Format::center(console_width, str),
+        Align::Right => Format::right(console_width, str),
+        Align::Left => Format::left(console_width, str),
+    });
+    print_lines(lines)?;
+    failures(failed)
+}
+
+/// Exit code 6 once the stats of the other modules have been printed.
+fn failures(failed: usize) -> Result<(), Error> {
+    match failed {
+        0 => Ok(()),
+        failed => Err(Error::ModulesFailed { failed }),
+    }
+}
+
+/// `ff config check`: prints every problem and fails if there are any.
+fn check_config(config_file: Option<&std::path::Path>) -> Result<(), Error> {
+    let report = config::check::check(config_file)?;
+    let mut lines = report_lines(&report);
+    if report.problems.is_empty() {
+        lines.push(match report.files.len() {
+            0 => "no config files found, the built-in defaults are used".to_string(),
+            1 => "checked 1 file, no problems found".to_string(),
+            files => format!("checked {} files, no problems found", files),
+        });
+    }
+    print_lines(lines.iter())?;
+    match report.problems.len() {
+        0 => Ok(()),
+        problems => Err(Error::ConfigCheck { problems }),
+    }
+}
+
+/// The problems and notes of a config check, one per line.
+fn report_lines(report: &config::check::Report) -> Vec<String> {
+    let mut lines = Vec::new();
+    for problem in &report.problems {
+        lines.push(format!(
+            "{}: {} {}",
+            problem.position(),
+            Chalk::colorize("error:", 0xF38BA8, true),
+            problem.message
+        ));
+    }
+    for note in &report.notes {
+        lines.push(format!("{} {}", Chalk::colorize("note:", 0x9FA1C9, true), note));
+    }
+    lines
+}
+
+/// `ff list modules`: every built-in module and whether it works here.
+fn list_modules(config: &Config) -> Result<(), Error> {
+    let infos: Vec<&modules::ModuleInfo> = modules::REGISTRY.iter().collect();
+    let collected = modules::probe(&infos, Duration::from_millis(config.timeout));
+    let rows = infos
+        .iter()
+        .zip(&collected)
+        .map(|(info, collected)| {
+            let available = match collected {
+                Collected::Stats(_) => "yes".to_string(),
+                Collected::Unavailable(err) | Collected::Failed(err) => format!("no ({})", err),
+                Collected::TimedOut(timeout) => format!("no (timed out after {} ms)", timeout.as_millis()),
+            };
+            vec![info.name.to_string(), available, info.stats.len().to_string(), info.description.to_string()]
+        })
+        .collect();
+    print_lines(table(&["MODULE", "AVAILABLE", "STATS", "DESCRIPTION"], rows, config).iter())
+}
+
+/// `ff list stats MODULE`: the stats of a built-in module and whether this
+/// machine reports them.
+fn list_stats(config: &Config, name: &str) -> Result<(), Error> {
+    let info = modules::find(name).ok_or_else(|| {
+        let names = modules::REGISTRY.iter().map(|info| info.name);
+        Error::Usage(format!("unknown module `{}`{}", name, suggest::did_you_mean(name, names)))
+    })?;
+    let collected = modules::probe(&[info], Duration::from_millis(config.timeout)).remove(0);
+    let rows = info
+        .stats
+        .iter()
+        .map(|stat| {
+            vec![
+                stat.name.to_string(),
+                if stat.unit.is_empty() { "-" } else { stat.unit }.to_string(),
+                if collected.reports(stat.name) { "yes" } else { "no" }.to_string(),
+                stat.description.to_string(),
+            ]
+        })
+        .collect();
+    print_lines(table(&["STAT", "UNIT", "AVAILABLE", "DESCRIPTION"], rows, config).iter())
+}
+
+/// Rows padded into columns under a header, in the colours of the theme.
+fn table(header: &[&str], rows: Vec<Vec<String>>, config: &Config) -> Vec<String> {
+    let mut widths: Vec<usize> = header.iter().map(|title| title.len()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
         }
     }
-    for str in str.lines() {
-        println!(
-            "{}",
-            match CONFIG.read().unwrap()["theme"]["align"].as_str().unwrap() {
-                "center" => Format::center(Format::console_width(), str),
-                "right" => Format::right(Format::console_width(), str),
-                _ => Format::left(Format::console_width(), str),
-            }
-        )
+    let line = |cells: Vec<String>, colors: &dyn Fn(usize) -> (u32, bool)| {
+        let last = cells.len() - 1;
+        let cells = cells.into_iter().enumerate().map(|(index, cell)| {
+            let padding = if index == last { 0 } else { widths[index] - cell.chars().count() + 2 };
+            let (color, bold) = colors(index);
+            format!("{}{}", Chalk::colorize(&cell, color, bold), " ".repeat(padding))
+        });
+        cells.collect::<String>()
+    };
+    let text = &config.theme.text;
+    let mut lines = vec![line(header.iter().map(|title| title.to_string()).collect(), &|_| (text.headers.0, true))];
+    for row in rows {
+        lines.push(line(row, &|index| if index == 0 { (text.key.0, false) } else { (text.value.0, false) }));
+    }
+    lines
+}
+
+/// The items of a comma separated list given on the command line.
+fn split_list(list: &str) -> Vec<String> {
+    list.split(',').map(str::trim).filter(|item| !item.is_empty()).map(String::from).collect()
+}
+
+fn warn(message: &str) {
+    eprintln!("{} {}", Chalk::colorize("warning:", 0xF9E2AF, true), message);
+}
+
+/// Writes `lines` to stdout. A closed pipe (`ff | head`) is not an error.
+fn print_lines<T: std::fmt::Display>(mut lines: impl Iterator<Item = T>) -> Result<(), Error> {
+    let mut stdout = std::io::stdout().lock();
+    let result = lines
+        .try_for_each(|line| writeln!(stdout, "{}", line))
+        .and_then(|_| stdout.flush());
+    match result {
+        Err(err) if err.kind() != std::io::ErrorKind::BrokenPipe => Err(Error::Render(err)),
+        _ => Ok(()),
     }
 }
@@ -1,96 +1,129 @@
 #![allow(dead_code)]
 use battery::Manager;
-use crate::modules::module_trait::Module;
+use crate::modules::module_trait::{Module, ModuleError};
+use crate::modules::stat::{Stat, StatGroup, StatInfo, StatValue};
+
+/// Stats this module can report for every battery.
+pub const STATS: &[StatInfo] = &[
+    StatInfo {
+        name: "percent",
+        description: "Charge of the battery.",
+        unit: "percent",
+        example: "87.0%",
+    },
+    StatInfo {
+        name: "status",
+        description: "Whether the battery is charging, discharging or full.",
+        unit: "",
+        example: "Discharging",
+    },
+    StatInfo {
+        name: "time",
+        description: "Time until the battery is full or empty.",
+        unit: "seconds",
+        example: "02:45",
+    },
+    StatInfo {
+        name: "energy",
+        description: "Energy stored in the battery.",
+        unit: "Wh",
+        example: "48.2 Wh",
+    },
+    StatInfo {
+        name: "energy_full",
+        description: "Energy stored when the battery is full.",
+        unit: "Wh",
+        example: "55.4 Wh",
+    },
+    StatInfo {
+        name: "energy_full_design",
+        description: "Energy the battery stored when it was new.",
+        unit: "Wh",
+        example: "57.0 Wh",
+    },
+    StatInfo {
+        name: "energy_rate",
+        description: "Power flowing into or out of the battery.",
+        unit: "W",
+        example: "9.3 W",
+    },
+];
 
 pub struct Battery {
     manager: Manager,
-    battery: battery::Battery,
+    batteries: Vec<battery::Battery>,
 }
 impl Battery {
-    pub fn new() -> Battery {
-        let manager = Manager::new().unwrap();
-        let battery = manager.batteries().unwrap().next().unwrap().unwrap();
-        Battery { manager, battery }
-    }
-    pub fn percentage(&self) -> String {
-        format!(
-            "{:.1}%{}",
-            self.battery.state_of_charge().value * 100.0,
-            if self.battery.state() == battery::State::Charging {
-                " "
-            } else {
-                ""
-            }
-        )
-    }
-    pub fn status(&self) -> String {
-        self.battery.state().to_string()
-    }
-    pub fn time_to_full(&self) -> String {
-        match self.battery.time_to_full() {
-            Some(time) => {
-                let duration = std::time::Duration::from_secs(time.value as u64);
-                let hours = duration.as_secs() / 3600;
-                let minutes = (duration.as_secs() % 3600) / 60;
-                format!("{:02}:{:02}", hours, minutes)
-            },
-            None => "".to_string()
+    pub fn new() -> Result<Battery, ModuleError> {
+        let manager = Manager::new()
+            .map_err(|err| ModuleError::new(format!("battery information is unavailable: {}", err)))?;
+        let batteries = manager
+            .batteries()
+            .map_err(|err| ModuleError::new(format!("cannot list batteries: {}", err)))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| ModuleError::new(format!("cannot read battery: {}", err)))?;
+        if batteries.is_empty() {
+            return Err(ModuleError::new("no battery found"));
         }
+        Ok(Battery { manager, batteries })
     }
-    pub fn time_to_empty(&self) -> String {
-        match self.battery.time_to_empty() {
-            Some(time) => {
-                let duration = std::time::Duration::from_secs(time.value as u64);
-                let hours = duration.as_secs() / 3600;
-                let minutes = (duration.as_secs() % 3600) / 60;
-                format!("{:02}:{:02}", hours, minutes)
+    pub fn handle(&mut self, battery_module: &[String]) -> Vec<StatGroup> {
+        let mut battery_groups: Vec<StatGroup> = Vec::new();
+        for (index, battery) in self.batteries.iter().enumerate() {
+            let mut battery_stats: Vec<Stat> = Vec::new();
+            for stat in battery_module {
+                battery_stats.push(Stat::new(stat, match stat.as_str() {
+                    "percent" => StatValue::Percent(battery.percentage() as f64),
+                    "status" => StatValue::Text(battery.status()),
+                    "time" => {
+                        let time = if battery.state() == battery::State::Charging {
+                            battery.time_until_full()
+                        } else {
+                            battery.time_until_empty()
+                        };
+                        time.map_or(StatValue::Missing, StatValue::Duration)
+                    }
+                    "energy" => StatValue::Energy(battery.energy().value as f64),
+                    "energy_full" => StatValue::Energy(battery.energy_full().value as f64),
+                    "energy_full_design" => StatValue::Energy(battery.energy_full_design().value as f64),
+                    "energy_rate" => StatValue::Power(battery.energy_rate().value as f64),
+                    _ => StatValue::Unsupported
+                }));
             }
-            None => "".to_string()
+            battery_groups.push(StatGroup {
+                // a single battery needs no sub-header
+                title: (self.batteries.len() > 1).then(|| format!("Battery {}", index)),
+                stats: battery_stats,
+            });
         }
+        battery_groups
     }
-    pub fn energy(&self) -> f32 {
-        self.battery.energy().value
-    }
-    pub fn energy_full(&self) -> f32 {
-        self.battery.energy_full().value
+}
+trait BatteryTrait {
+    fn percentage(&self) -> f32;
+    fn status(&self) -> String;
+    fn time_until_full(&self) -> Option<std::time::Duration>;
+    fn time_until_empty(&self) -> Option<std::time::Duration>;
+}
+impl BatteryTrait for battery::Battery {
+    fn percentage(&self) -> f32 {
+        self.state_of_charge().value * 100.0
     }
-    pub fn energy_full_design(&self) -> f32 {
-        self.battery.energy_full_design().value
+    fn status(&self) -> String {
+        self.state().to_string()
     }
-    pub fn energy_rate(&self) -> f32 {
-        self.battery.energy_rate().value
+    fn time_until_full(&self) -> Option<std::time::Duration> {
+        self.time_to_full()
+            .map(|time| std::time::Duration::from_secs(time.value as u64))
     }
-    pub fn handle(&mut self, battery_module: &Vec<serde_json::Value>) -> Vec<std::collections::HashMap<String, String>> {
-        if !(self.manager.batteries().unwrap().count() > 0) {
-            return vec![std::collections::HashMap::new()];
-        }
-        let mut battery_stats: Vec<std::collections::HashMap<String, String>> = Vec::new();
-        for stat in battery_module {
-            let mut stat_map: std::collections::HashMap<String, String> = std::collections::HashMap::new();
-            stat_map.insert(stat.as_str().unwrap().to_string(), match stat.as_str().unwrap() {
-                "percent" => self.percentage(),
-                "status" => self.status(),
-                "time" => {
-                    if self.status() == "charging" {
-                        self.time_to_full()
-                    } else {
-                        self.time_to_empty()
-                    }
-                }
-                "energy" => format!("{:.1} Wh", self.energy()),
-                "energy_full" => format!("{:.1} Wh", self.energy_full()),
-                "energy_full_design" => format!("{:.1} Wh", self.energy_full_design()),
-                "energy_rate" => format!("{:.1} W", self.energy_rate()),
-                _ => "".to_string()
-            });
-            battery_stats.push(stat_map);
-        }
-        battery_stats
+    fn time_until_empty(&self) -> Option<std::time::Duration> {
+        self.time_to_empty()
+            .map(|time| std::time::Duration::from_secs(time.value as u64))
     }
 }
 
 impl Module for Battery {
-    fn handle(&mut self, module: &Vec<serde_json::Value>) -> Vec<std::collections::HashMap<String, String>> {
-        Battery::handle(self, module)
+    fn handle(&mut self, stats: &[String]) -> Vec<StatGroup> {
+        Battery::handle(self, stats)
     }
 }
\ No newline at end of file
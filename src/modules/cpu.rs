@@ -1,28 +1,85 @@
 #![allow(dead_code)]
 use sysinfo::System;
-use crate::modules::module_trait::Module;
+use crate::modules::module_trait::{Module, ModuleError};
+use crate::modules::stat::{Stat, StatGroup, StatInfo, StatValue};
+
+/// Stats this module can report, in the order of the default config.
+pub const STATS: &[StatInfo] = &[
+    StatInfo {
+        name: "name",
+        description: "Brand name of the processor.",
+        unit: "",
+        example: "Intel(R) Core(TM) i7-8565U CPU @ 1.80GHz",
+    },
+    StatInfo {
+        name: "cores",
+        description: "Number of physical cores.",
+        unit: "",
+        example: "4",
+    },
+    StatInfo {
+        name: "threads",
+        description: "Number of logical processors.",
+        unit: "",
+        example: "8",
+    },
+    StatInfo {
+        name: "frequency",
+        description: "Current clock frequency of the first core.",
+        unit: "MHz",
+        example: "1992 MHz",
+    },
+    StatInfo {
+        name: "usage",
+        description: "Load over all cores, measured over a short interval.",
+        unit: "percent",
+        example: "12.5%",
+    },
+    StatInfo {
+        name: "vendor",
+        description: "Vendor identifier reported by the processor.",
+        unit: "",
+        example: "GenuineIntel",
+    },
+    StatInfo {
+        name: "architecture",
+        description: "Instruction set the program was built for.",
+        unit: "",
+        example: "x86_64",
+    },
+];
 
 pub struct Cpu {
     system: System,
+    usage: Option<f32>,
 }
 impl Cpu {
-    pub fn new() -> Cpu {
+    pub fn new() -> Result<Cpu, ModuleError> {
         let mut system = System::new();
         system.refresh_cpu_all();
-        Cpu { system }
+        if system.cpus().is_empty() {
+            return Err(ModuleError::new("no CPU information available"));
+        }
+        Ok(Cpu { system, usage: None })
     }
-    pub fn cpu_usage(&mut self) -> String {
+    /// Usage needs two samples taken some time apart, so it is measured
+    /// once per module instance and reused afterwards.
+    pub fn cpu_usage(&mut self) -> f32 {
+        if let Some(usage) = self.usage {
+            return usage;
+        }
         self.system.refresh_cpu_usage();
-        std::thread::sleep(std::time::Duration::from_millis(200));
+        std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
         self.system.refresh_cpu_usage();
-        let cpu_usage: String = format!("{:.1}%", self.system.global_cpu_usage());
-        cpu_usage
+        let usage = self.system.global_cpu_usage();
+        self.usage = Some(usage);
+        usage
     }
     pub fn name(&self) -> String {
         self.system.cpus()[0].brand().trim().to_string()
     }
-    pub fn cores(&self) -> u8 {
-        self.system.physical_core_count().unwrap() as u8
+    pub fn cores(&self) -> Option<u8> {
+        self.system.physical_core_count().map(|cores| cores as u8)
     }
     pub fn frequency(&self) -> u32 {
         self.system.cpus()[0].frequency() as u32
@@ -36,27 +93,25 @@ impl Cpu {
     pub fn vendor(&self) -> String {
         self.system.cpus()[0].vendor_id().to_string()
     }
-    pub fn handle(&mut self, cpu_module: &Vec<serde_json::Value>) -> Vec<std::collections::HashMap<String, String>> {
-        let mut cpu_stats: Vec<std::collections::HashMap<String, String>> = Vec::new();
+    pub fn handle(&mut self, cpu_module: &[String]) -> Vec<Stat> {
+        let mut cpu_stats: Vec<Stat> = Vec::new();
         for stat in cpu_module {
-            let mut stat_map: std::collections::HashMap<String, String> = std::collections::HashMap::new();
-            stat_map.insert(stat.as_str().unwrap().to_string(), match stat.as_str().unwrap() {
-                "name" => self.name(),
-                "cores" => self.cores().to_string(),
-                "threads" => self.threads().to_string(),
-                "speed" => self.frequency().to_string(),
-                "usage" => self.cpu_usage().to_string(),
-                "vendor" => self.vendor(),
-                "architecture" => self.architecture(),
-                _ => "".to_string()
-            });
-            cpu_stats.push(stat_map);
+            cpu_stats.push(Stat::new(stat, match stat.as_str() {
+                "name" => StatValue::Text(self.name()),
+                "cores" => self.cores().map_or(StatValue::Missing, |cores| StatValue::Count(cores as u64)),
+                "threads" => StatValue::Count(self.threads() as u64),
+                "frequency" => StatValue::Frequency(self.frequency() as u64),
+                "usage" => StatValue::Percent(self.cpu_usage() as f64),
+                "vendor" => StatValue::Text(self.vendor()),
+                "architecture" => StatValue::Text(self.architecture()),
+                _ => StatValue::Unsupported
+            }));
         }
         cpu_stats
     }
 }
 impl Module for Cpu {
-    fn handle(&mut self, module: &Vec<serde_json::Value>) -> Vec<std::collections::HashMap<String, String>> {
-        Cpu::handle(self, module)
+    fn handle(&mut self, stats: &[String]) -> Vec<StatGroup> {
+        vec![StatGroup::untitled(Cpu::handle(self, stats))]
     }
 }
\ No newline at end of file
@@ -1,15 +1,18 @@
-use crossterm::style::PrintStyledContent;
+use std::fmt;
+use std::path::Path;
 use sysinfo::{
 	Disk as sysinfo_disk,
 	Disks as sysinfo_disks,
 };
-use crate::modules::module_trait::Module;
+use crate::modules::module_trait::{Module, ModuleError};
+use crate::modules::stat::{Stat, StatGroup, StatInfo, StatValue};
 pub enum DiskUnit {
 	B,
 	KB,
 	MB,
 	GB,
 }
+#[allow(clippy::upper_case_acronyms)]
 pub enum DiskType {
 	HDD,
 	SSD,
@@ -19,15 +22,17 @@ pub enum DiskState {
 	Active,
 	Inactive,
 }
-impl DiskUnit {
-	pub fn to_string(&self) -> String {
-		match self {
-			DiskUnit::B => "B".to_string(),
-			DiskUnit::KB => "KB".to_string(),
-			DiskUnit::MB => "MB".to_string(),
-			DiskUnit::GB => "GB".to_string(),
-		}
+impl fmt::Display for DiskUnit {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match self {
+			DiskUnit::B => "B",
+			DiskUnit::KB => "KB",
+			DiskUnit::MB => "MB",
+			DiskUnit::GB => "GB",
+		})
 	}
+}
+impl DiskUnit {
 	pub fn to_u64(&self) -> u64 {
 		match self {
 			DiskUnit::B => 1,
@@ -40,57 +45,111 @@ impl DiskUnit {
 		value as f64 * from.to_u64() as f64 / to.to_u64() as f64
 	}
 }
-impl DiskType {
-	pub fn to_string(&self) -> String {
-		match self {
-			DiskType::HDD => "HDD".to_string(),
-			DiskType::SSD => "SSD".to_string(),
-			DiskType::NVME => "NVME".to_string(),
-		}
+impl fmt::Display for DiskType {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match self {
+			DiskType::HDD => "HDD",
+			DiskType::SSD => "SSD",
+			DiskType::NVME => "NVME",
+		})
 	}
 }
-impl DiskState {
-	pub fn to_string(&self) -> String {
-		match self {
-			DiskState::Active => "Active".to_string(),
-			DiskState::Inactive => "Inactive".to_string(),
-		}
+impl fmt::Display for DiskState {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match self {
+			DiskState::Active => "Active",
+			DiskState::Inactive => "Inactive",
+		})
 	}
 }
 
+/// Stats this module can report for every disk.
+pub const STATS: &[StatInfo] = &[
+	StatInfo {
+		name: "name",
+		description: "Device name of the disk.",
+		unit: "",
+		example: "/dev/nvme0n1p2",
+	},
+	StatInfo {
+		name: "mount_point",
+		description: "Where the disk is mounted.",
+		unit: "",
+		example: "/",
+	},
+	StatInfo {
+		name: "file_system",
+		description: "File system on the disk.",
+		unit: "",
+		example: "ext4",
+	},
+	StatInfo {
+		name: "total",
+		description: "Size of the disk.",
+		unit: "bytes",
+		example: "476.3 GB",
+	},
+	StatInfo {
+		name: "used",
+		description: "Space in use.",
+		unit: "bytes",
+		example: "201.7 GB",
+	},
+	StatInfo {
+		name: "free",
+		description: "Space not in use, including blocks reserved for root.",
+		unit: "bytes",
+		example: "274.6 GB",
+	},
+	StatInfo {
+		name: "available",
+		description: "Space available to unprivileged users.",
+		unit: "bytes",
+		example: "250.4 GB",
+	},
+	StatInfo {
+		name: "removable",
+		description: "Whether the disk can be removed.",
+		unit: "",
+		example: "false",
+	},
+];
+
 pub struct Disk {
 	disks: sysinfo_disks,
 }
 
 impl Disk {
-	pub fn new() -> Disk {
-		let mut disks = sysinfo_disks::new();
-		disks.refresh(false);
-		Disk { disks }
+	pub fn new() -> Result<Disk, ModuleError> {
+		let disks = sysinfo_disks::new_with_refreshed_list();
+		if disks.list().is_empty() {
+			return Err(ModuleError::new("no disks found"));
+		}
+		Ok(Disk { disks })
 	}
-	pub fn handle(&mut self, disk_module: &Vec<serde_json::Value>) -> Vec<std::collections::HashMap<String, String>> {
-		let mut disk_stats: Vec<std::collections::HashMap<String, String>> = Vec::new();
+	pub fn handle(&mut self, disk_module: &[String]) -> Vec<StatGroup> {
+		let mut disk_groups: Vec<StatGroup> = Vec::new();
 		for disk in self.disks.iter() {
-			let mut disk_map: std::collections::HashMap<String, String> = std::collections::HashMap::new();
+			let mut disk_stats: Vec<Stat> = Vec::new();
 			for stat in disk_module {
-				disk_map.insert(stat.as_str().unwrap().to_string(), match stat.as_str().unwrap() {
-					"name" => disk.diskname(),
-					"mount_point" => disk.mountpoint(),
-					"file_system" => disk.filesystem(),
-					"total" => disk.total(None).to_string(),
-					"used" => disk.used(None).to_string(),
-					"free" => disk.free(None).to_string(),
-					"available" => disk.available(None).to_string(),
-					"removable" => disk.removable().to_string(),
-					_ => "".to_string()
-				});
+				disk_stats.push(Stat::new(stat, match stat.as_str() {
+					"name" => StatValue::Text(disk.diskname()),
+					"mount_point" => StatValue::Text(disk.mountpoint()),
+					"file_system" => StatValue::Text(disk.filesystem()),
+					"total" => StatValue::Bytes(disk.total(None) as u64),
+					"used" => StatValue::Bytes(disk.used(None) as u64),
+					"free" => disk.free(None).map_or(StatValue::Missing, |free| StatValue::Bytes(free as u64)),
+					"available" => StatValue::Bytes(disk.available(None) as u64),
+					"removable" => StatValue::Bool(disk.removable()),
+					_ => StatValue::Unsupported
+				}));
 			}
-			disk_stats.push(disk_map);
-			let mut disk_map = std::collections::HashMap::new();
-			disk_map.insert("".to_string(), "".to_string());
-			disk_stats.push(disk_map);
+			disk_groups.push(StatGroup {
+				title: Some(disk.mountpoint()),
+				stats: disk_stats,
+			});
 		}
-		disk_stats
+		disk_groups
 	}
 }
 trait DiskTrait {
@@ -99,19 +158,19 @@ trait DiskTrait {
 	fn filesystem(&self) -> String;
 	fn total(&self, unit: Option<DiskUnit>) -> f64;
 	fn used(&self, unit: Option<DiskUnit>) -> f64;
-	fn free(&self, unit: Option<DiskUnit>) -> f64;
+	fn free(&self, unit: Option<DiskUnit>) -> Option<f64>;
 	fn available(&self, unit: Option<DiskUnit>) -> f64;
 	fn removable(&self) -> bool;
 }
 impl DiskTrait for sysinfo_disk {
 	fn diskname(&self) -> String {
-		self.name().to_str().unwrap().to_string()
+		self.name().to_string_lossy().into_owned()
 	}
 	fn mountpoint(&self) -> String {
-		self.mount_point().to_str().unwrap().to_string()
+		self.mount_point().to_string_lossy().into_owned()
 	}
 	fn filesystem(&self) -> String {
-		self.file_system().to_str().unwrap().to_string()
+		self.file_system().to_string_lossy().into_owned()
 	}
 	fn total(&self, unit: Option<DiskUnit>) -> f64 {
 		match unit {
@@ -121,15 +180,16 @@ impl DiskTrait for sysinfo_disk {
 	}
 	fn used(&self, unit: Option<DiskUnit>) -> f64 {
 		match unit {
-			Some(unit) => DiskUnit::convert(DiskUnit::B, unit, self.total_space() - self.available_space()),
-			None => (self.total_space() - self.available_space()) as f64,
+			Some(unit) => DiskUnit::convert(DiskUnit::B, unit, self.total_space().saturating_sub(self.available_space())),
+			None => (self.total_space().saturating_sub(self.available_space())) as f64,
 		}
 	}
-	fn free(&self, unit: Option<DiskUnit>) -> f64 {
-		match unit {
-			Some(unit) => DiskUnit::convert(DiskUnit::B, unit, self.available_space()),
-			None => self.available_space() as f64,
-		}
+	fn free(&self, unit: Option<DiskUnit>) -> Option<f64> {
+		let free = free_space(self.mount_point())?;
+		Some(match unit {
+			Some(unit) => DiskUnit::convert(DiskUnit::B, unit, free),
+			None => free as f64,
+		})
 	}
 	fn available(&self, unit: Option<DiskUnit>) -> f64 {
 		match unit {
@@ -142,8 +202,33 @@ impl DiskTrait for sysinfo_disk {
 	}
 }
 
+/// Free bytes on the file system mounted at `mount_point`, counting the
+/// blocks reserved for root that `available_space` leaves out.
+#[cfg(unix)]
+fn free_space(mount_point: &Path) -> Option<u64> {
+	use std::ffi::CString;
+	use std::os::unix::ffi::OsStrExt;
+	let path = CString::new(mount_point.as_os_str().as_bytes()).ok()?;
+	let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+	// SAFETY: `path` is NUL-terminated and `stat` is only read after
+	// statvfs reports that it filled it in.
+	if unsafe { libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+		return None;
+	}
+	let stat = unsafe { stat.assume_init() };
+	// The field widths differ between platforms.
+	#[allow(clippy::unnecessary_cast)]
+	Some((stat.f_bfree as u64).saturating_mul(stat.f_frsize as u64))
+}
+
+/// Only Unix exposes the free-block count; elsewhere the stat is missing.
+#[cfg(not(unix))]
+fn free_space(_mount_point: &Path) -> Option<u64> {
+	None
+}
+
 impl Module for Disk {
-	fn handle(&mut self, module: &Vec<serde_json::Value>) -> Vec<std::collections::HashMap<String, String>> {
-		Disk::handle(self, module)
+	fn handle(&mut self, stats: &[String]) -> Vec<StatGroup> {
+		Disk::handle(self, stats)
 	}
 }
\ No newline at end of file
@@ -1,21 +1,25 @@
 #![allow(dead_code)]
+use std::fmt;
 use sysinfo::System;
 use crate::modules::module_trait::Module;
+use crate::modules::stat::{Stat, StatGroup, StatInfo, StatValue};
 pub enum MemoryUnit {
     B,
     KB,
     MB,
     GB,
 }
-impl MemoryUnit {
-    pub fn to_string(&self) -> String {
-        match self {
-            MemoryUnit::B => "B".to_string(),
-            MemoryUnit::KB => "KB".to_string(),
-            MemoryUnit::MB => "MB".to_string(),
-            MemoryUnit::GB => "GB".to_string(),
-        }
+impl fmt::Display for MemoryUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            MemoryUnit::B => "B",
+            MemoryUnit::KB => "KB",
+            MemoryUnit::MB => "MB",
+            MemoryUnit::GB => "GB",
+        })
     }
+}
+impl MemoryUnit {
     pub fn to_u64(&self) -> u64 {
         match self {
             MemoryUnit::B => 1,
@@ -29,6 +33,58 @@ impl MemoryUnit {
     }
 }
 
+/// Stats this module can report, in the order of the default config.
+pub const STATS: &[StatInfo] = &[
+    StatInfo {
+        name: "total",
+        description: "Installed memory.",
+        unit: "bytes",
+        example: "15.5 GB",
+    },
+    StatInfo {
+        name: "used",
+        description: "Memory in use.",
+        unit: "bytes",
+        example: "6.2 GB",
+    },
+    StatInfo {
+        name: "free",
+        description: "Memory not in use.",
+        unit: "bytes",
+        example: "9.3 GB",
+    },
+    StatInfo {
+        name: "available",
+        description: "Memory that can be given to new programs, including caches that can be dropped.",
+        unit: "bytes",
+        example: "8.7 GB",
+    },
+    StatInfo {
+        name: "swap_total",
+        description: "Size of the swap space.",
+        unit: "bytes",
+        example: "2.0 GB",
+    },
+    StatInfo {
+        name: "swap_used",
+        description: "Swap space in use.",
+        unit: "bytes",
+        example: "256.0 MB",
+    },
+    StatInfo {
+        name: "swap_free",
+        description: "Swap space not in use.",
+        unit: "bytes",
+        example: "1.8 GB",
+    },
+    StatInfo {
+        name: "percent",
+        description: "Share of the memory in use.",
+        unit: "percent",
+        example: "40.0%",
+    },
+];
+
 pub struct Memory {
     system: System,
 }
@@ -53,14 +109,14 @@ impl Memory {
     }
     pub fn free(&self, unit: Option<MemoryUnit>) -> f64 {
         match unit {
-            Some(unit) => MemoryUnit::convert(MemoryUnit::B, unit, self.system.total_memory() - self.system.used_memory()),
+            Some(unit) => MemoryUnit::convert(MemoryUnit::B, unit, self.system.total_memory().saturating_sub(self.system.used_memory())),
             None => self.system.total_memory() as f64 - self.system.used_memory() as f64,
         }
     }
     pub fn available(&self, unit: Option<MemoryUnit>) -> f64 {
         match unit {
-            Some(unit) => MemoryUnit::convert(MemoryUnit::B, unit, self.system.total_memory() - self.system.used_memory() - self.system.total_swap()),
-            None => self.system.total_memory() as f64 - self.system.used_memory() as f64 - self.system.total_swap() as f64,
+            Some(unit) => MemoryUnit::convert(MemoryUnit::B, unit, self.system.available_memory()),
+            None => self.system.available_memory() as f64,
         }
     }
     pub fn swap_total(&self, unit: Option<MemoryUnit>) -> f64 {
@@ -77,33 +133,31 @@ impl Memory {
     }
     pub fn swap_free(&self, unit: Option<MemoryUnit>) -> f64 {
         match unit {
-            Some(unit) => MemoryUnit::convert(MemoryUnit::B, unit, self.system.total_swap() - self.system.used_swap()),
+            Some(unit) => MemoryUnit::convert(MemoryUnit::B, unit, self.system.total_swap().saturating_sub(self.system.used_swap())),
             None => self.system.total_swap() as f64 - self.system.used_swap() as f64,
         }
     }
-    pub fn handle(&self, memory_module: &Vec<serde_json::Value>) -> Vec<std::collections::HashMap<String, String>> {
-        let mut memory_stats: Vec<std::collections::HashMap<String, String>> = Vec::new();
+    pub fn handle(&self, memory_module: &[String]) -> Vec<Stat> {
+        let mut memory_stats: Vec<Stat> = Vec::new();
         for stat in memory_module {
-            let mut stat_map: std::collections::HashMap<String, String> = std::collections::HashMap::new();
-            stat_map.insert(stat.as_str().unwrap().to_string(), match stat.as_str().unwrap() {
-                "total" => format!("{:.1} {}", self.total(Some(MemoryUnit::GB)), MemoryUnit::GB.to_string()),
-                "used" => format!("{:.1} {}", self.used(Some(MemoryUnit::GB)), MemoryUnit::GB.to_string()),
-                "free" => format!("{:.1} {}", self.free(Some(MemoryUnit::GB)), MemoryUnit::GB.to_string()),
-                "available" => format!("{:.1} {}", self.available(Some(MemoryUnit::GB)), MemoryUnit::GB.to_string()),
-                "swap_total" => format!("{:.1} {}", self.swap_total(Some(MemoryUnit::GB)), MemoryUnit::GB.to_string()),
-                "swap_used" => format!("{:.1} {}", self.swap_used(Some(MemoryUnit::GB)), MemoryUnit::GB.to_string()),
-                "swap_free" => format!("{:.1} {}", self.swap_free(Some(MemoryUnit::GB)), MemoryUnit::GB.to_string()),
-                "percent" => format!("{:.1}%", self.used(None) / self.total(None) * 100.0),
-                _ => "".to_string()
-            });
-            memory_stats.push(stat_map);
+            memory_stats.push(Stat::new(stat, match stat.as_str() {
+                "total" => StatValue::Bytes(self.total(None) as u64),
+                "used" => StatValue::Bytes(self.used(None) as u64),
+                "free" => StatValue::Bytes(self.free(None) as u64),
+                "available" => StatValue::Bytes(self.available(None) as u64),
+                "swap_total" => StatValue::Bytes(self.swap_total(None) as u64),
+                "swap_used" => StatValue::Bytes(self.swap_used(None) as u64),
+                "swap_free" => StatValue::Bytes(self.swap_free(None) as u64),
+                "percent" => StatValue::Percent(self.used(None) / self.total(None) * 100.0),
+                _ => StatValue::Unsupported
+            }));
         }
         memory_stats
     }
 }
 
 impl Module for Memory {
-    fn handle(&mut self, module: &Vec<serde_json::Value>) -> Vec<std::collections::HashMap<String, String>> {
-        Memory::handle(self, module)
+    fn handle(&mut self, stats: &[String]) -> Vec<StatGroup> {
+        vec![StatGroup::untitled(Memory::handle(self, stats))]
     }
 }
\ No newline at end of file
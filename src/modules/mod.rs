@@ -3,4 +3,251 @@ pub mod cpu;
 pub mod module_trait;
 pub mod memory;
 pub mod battery;
-pub mod disks;
\ No newline at end of file
+pub mod disks;
+pub mod plugin;
+pub mod stat;
+
+use module_trait::{Module, ModuleError};
+use stat::{StatGroup, StatInfo, StatValue};
+use std::any::Any;
+use std::cell::Cell;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::{mpsc, Once};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A built-in module that can be referenced by `name` in the config.
+/// Modules are only constructed when the config asks for them.
+pub struct ModuleInfo {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Stats accepted in the module's `stats` list.
+    pub stats: &'static [StatInfo],
+    /// Name of the module in metric names, e.g. `disk` in
+    /// `ff_disk_used_bytes`.
+    pub metric: &'static str,
+    /// Stats that tell the groups of the module apart and label its metrics
+    /// instead of being metrics themselves. `index` is the position of the
+    /// group, for modules without such a stat.
+    pub labels: &'static [&'static str],
+    pub create: fn() -> Result<Box<dyn Module>, ModuleError>,
+}
+
+pub const REGISTRY: &[ModuleInfo] = &[
+    ModuleInfo {
+        name: "CPU",
+        description: "The processor: model, cores, clock frequency and load.",
+        stats: cpu::STATS,
+        metric: "cpu",
+        labels: &[],
+        create: || Ok(Box::new(cpu::Cpu::new()?)),
+    },
+    ModuleInfo {
+        name: "Memory",
+        description: "Physical memory and swap space.",
+        stats: memory::STATS,
+        metric: "memory",
+        labels: &[],
+        create: || Ok(Box::new(memory::Memory::new())),
+    },
+    ModuleInfo {
+        name: "Battery",
+        description: "Charge and health of every battery, one group per battery.",
+        stats: battery::STATS,
+        metric: "battery",
+        labels: &["index"],
+        create: || Ok(Box::new(battery::Battery::new()?)),
+    },
+    ModuleInfo {
+        name: "Disks",
+        description: "Size and usage of every mounted disk, one group per disk.",
+        stats: disks::STATS,
+        metric: "disk",
+        labels: &["mount_point", "file_system"],
+        create: || Ok(Box::new(disks::Disk::new()?)),
+    },
+];
+
+impl ModuleInfo {
+    pub fn stat_names(&self) -> impl Iterator<Item = &'static str> {
+        self.stats.iter().map(|stat| stat.name)
+    }
+    pub fn has_stat(&self, name: &str) -> bool {
+        self.stats.iter().any(|stat| stat.name == name)
+    }
+    pub fn stat(&self, name: &str) -> Option<&'static StatInfo> {
+        self.stats.iter().find(|stat| stat.name == name)
+    }
+}
+
+pub fn find(name: &str) -> Option<&'static ModuleInfo> {
+    REGISTRY.iter().find(|info| info.name == name)
+}
+
+/// What a configured module produced for this run.
+#[derive(Debug, Clone)]
+pub enum Collected {
+    Stats(Vec<StatGroup>),
+    Unavailable(ModuleError),
+    /// The module ran but could not produce its stats, e.g. a plugin that
+    /// exited with an error.
+    Failed(ModuleError),
+    /// The module did not answer within its configured timeout.
+    TimedOut(Duration),
+}
+
+impl Collected {
+    /// Whether any group has a value for `stat`.
+    pub fn reports(&self, stat: &str) -> bool {
+        match self {
+            Collected::Stats(groups) => groups.iter().flat_map(|group| &group.stats).any(|reported| {
+                reported.name == stat && !matches!(reported.value, StatValue::Missing | StatValue::Unsupported)
+            }),
+            _ => false,
+        }
+    }
+}
+
+/// Collects every stat of the built-in modules `infos`, to find out which
+/// of them this machine can report.
+pub fn probe(infos: &[&'static ModuleInfo], timeout: Duration) -> Vec<Collected> {
+    collect_all(
+        infos
+            .iter()
+            .map(|info| Job {
+                name: info.name.to_string(),
+                source: Source::Builtin(info),
+                stats: info.stat_names().map(String::from).collect(),
+                timeout,
+            })
+            .collect(),
+    )
+}
+
+/// Where the stats of a configured module come from.
+#[derive(Clone)]
+pub enum Source {
+    Builtin(&'static ModuleInfo),
+    Plugin(PathBuf),
+}
+
+/// A module to collect, together with the stats requested for it and how
+/// long the caller is willing to wait.
+#[derive(Clone)]
+pub struct Job {
+    pub name: String,
+    pub source: Source,
+    pub stats: Vec<String>,
+    pub timeout: Duration,
+}
+
+/// Constructs the module and collects the requested stats from it.
+pub fn collect(job: &Job) -> Collected {
+    match &job.source {
+        Source::Builtin(info) => match (info.create)() {
+            Ok(mut module) => Collected::Stats(module.handle(&job.stats)),
+            Err(err) => Collected::Unavailable(err),
+        },
+        Source::Plugin(path) => match plugin::run(&job.name, path, &job.stats, job.timeout) {
+            Ok(groups) => Collected::Stats(groups),
+            Err(err) => Collected::Failed(err),
+        },
+    }
+}
+
+thread_local! {
+    /// Set on the worker threads of [`collect_all`], whose panics are
+    /// reported as failed modules rather than printed.
+    static COLLECTING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Collects every job on its own worker thread and returns the results in
+/// the order of `jobs`. A module that panics has failed, with the panic
+/// message as the reason. All timeouts are measured from the same starting
+/// point, so the total wait is bounded by the slowest module rather than
+/// the sum of all of them. Threads of modules that time out are left to
+/// finish in the background.
+pub fn collect_all(jobs: Vec<Job>) -> Vec<Collected> {
+    static QUIET_WORKERS: Once = Once::new();
+    QUIET_WORKERS.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !COLLECTING.with(Cell::get) {
+                previous(info);
+            }
+        }));
+    });
+    let start = Instant::now();
+    let pending: Vec<(mpsc::Receiver<Collected>, Duration)> = jobs
+        .into_iter()
+        .map(|job| {
+            let (sender, receiver) = mpsc::channel();
+            let timeout = job.timeout;
+            thread::spawn(move || {
+                COLLECTING.with(|collecting| collecting.set(true));
+                let collected = panic::catch_unwind(AssertUnwindSafe(|| collect(&job))).unwrap_or_else(|payload| {
+                    Collected::Failed(ModuleError::new(format!("the module panicked: {}", panic_message(&*payload))))
+                });
+                let _ = sender.send(collected);
+            });
+            (receiver, timeout)
+        })
+        .collect();
+    pending
+        .into_iter()
+        .map(|(receiver, timeout)| {
+            let remaining = (start + timeout).saturating_duration_since(Instant::now());
+            match receiver.recv_timeout(remaining) {
+                Ok(collected) => collected,
+                Err(mpsc::RecvTimeoutError::Timeout) => Collected::TimedOut(timeout),
+                // the worker died without unwinding, which catch_unwind
+                // cannot report
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    Collected::Failed(ModuleError::new("the module stopped unexpectedly"))
+                }
+            }
+        })
+        .collect()
+}
+
+/// The message a panic was started with, if it has one.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => payload.downcast_ref::<String>().map_or("no message", String::as_str),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static PANICS: ModuleInfo = ModuleInfo {
+        name: "Panics",
+        description: "",
+        stats: &[],
+        metric: "panics",
+        labels: &[],
+        create: || panic!("no {} here", "sensors"),
+    };
+
+    #[test]
+    fn a_panicking_module_fails_alone() {
+        let job = |source| Job {
+            name: "test".to_string(),
+            source,
+            stats: Vec::new(),
+            timeout: Duration::from_secs(10),
+        };
+        let collected = collect_all(vec![
+            job(Source::Builtin(&PANICS)),
+            job(Source::Plugin(PathBuf::from("/nonexistent/plugin"))),
+        ]);
+        match &collected[0] {
+            Collected::Failed(err) => assert_eq!(err.message, "the module panicked: no sensors here"),
+            _ => panic!("the module did not fail"),
+        }
+        assert!(matches!(&collected[1], Collected::Failed(err) if err.message.starts_with("cannot start plugin")));
+    }
+}
\ No newline at end of file
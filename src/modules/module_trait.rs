@@ -1,5 +1,28 @@
 use std::any::Any;
+use std::fmt;
+use crate::modules::stat::StatGroup;
 
 pub trait Module: Any {
-    fn handle(&mut self, module: &Vec<serde_json::Value>) -> Vec<std::collections::HashMap<String, String>>;
+    fn handle(&mut self, stats: &[String]) -> Vec<StatGroup>;
+}
+
+/// Returned by a module constructor when the hardware or OS facility it
+/// reads from is not present (no battery, no mounted disks, ...).
+#[derive(Debug, Clone)]
+pub struct ModuleError {
+    pub message: String,
+}
+
+impl ModuleError {
+    pub fn new(message: impl Into<String>) -> ModuleError {
+        ModuleError {
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ModuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
 }
\ No newline at end of file
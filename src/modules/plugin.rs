@@ -0,0 +1,271 @@
+//! Modules backed by an external executable.
+//!
+//! A config entry with a `plugin` path, e.g.
+//! `{"name": "Kube", "plugin": "/path/to/exe", "stats": ["pods"]}`, runs
+//! that executable once per invocation of `ff`.
+//!
+//! # Protocol, version 1
+//!
+//! `ff` writes a single JSON object to the plugin's stdin and closes it:
+//!
+//! ```json
+//! {"protocol": 1, "module": "Kube", "stats": ["pods", "nodes"]}
+//! ```
+//!
+//! The plugin prints a single JSON object to stdout and exits with status 0:
+//!
+//! ```json
+//! {
+//!     "protocol": 1,
+//!     "groups": [
+//!         {"title": "prod", "stats": [{"name": "pods", "value": 12}]},
+//!         {"title": "staging", "stats": [{"name": "pods", "value": 3}]}
+//!     ]
+//! }
+//! ```
+//!
+//! A plugin with a single group may answer `{"protocol": 1, "stats": [...]}`
+//! instead. `title` is optional. Each stat row has a `name`, a `value`
+//! (string, number, boolean or `null` for "no value") and an optional
+//! `unit` for numbers: `bytes`, `percent`, `MHz`, `seconds`, `Wh` or `W`.
+//! Rows are rendered in the order they are returned.
+//!
+//! Anything the plugin writes to stderr is shown to the user when it
+//! fails. A plugin that does not exit within the module's `timeout` is
+//! killed.
+use crate::modules::module_trait::ModuleError;
+use crate::modules::stat::{Stat, StatGroup, StatValue};
+use serde::Deserialize;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Response {
+    protocol: u32,
+    #[serde(default)]
+    groups: Vec<GroupResponse>,
+    #[serde(default)]
+    stats: Vec<StatResponse>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GroupResponse {
+    #[serde(default)]
+    title: Option<String>,
+    stats: Vec<StatResponse>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StatResponse {
+    name: String,
+    value: serde_json::Value,
+    #[serde(default)]
+    unit: Option<String>,
+}
+
+/// Runs the plugin at `path` and collects the requested stats from it.
+pub fn run(name: &str, path: &Path, stats: &[String], timeout: Duration) -> Result<Vec<StatGroup>, ModuleError> {
+    let mut child = Command::new(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| ModuleError::new(format!("cannot start plugin `{}`: {}", path.display(), err)))?;
+    let request = serde_json::json!({
+        "protocol": PROTOCOL_VERSION,
+        "module": name,
+        "stats": stats,
+    });
+    if let Some(mut stdin) = child.stdin.take() {
+        // a plugin that ignores its input may already have closed stdin
+        let _ = stdin.write_all(request.to_string().as_bytes());
+    }
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(ModuleError::new(format!(
+                    "plugin `{}` was killed after {} ms",
+                    path.display(),
+                    timeout.as_millis()
+                )));
+            }
+            Ok(None) => thread::sleep(Duration::from_millis(5)),
+            Err(err) => return Err(ModuleError::new(format!("cannot wait for plugin `{}`: {}", path.display(), err))),
+        }
+    };
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    let with_stderr = |message: String| {
+        let stderr = stderr.trim();
+        if stderr.is_empty() {
+            ModuleError::new(message)
+        } else {
+            ModuleError::new(format!("{}\n{}", message, stderr))
+        }
+    };
+    if !status.success() {
+        return Err(with_stderr(format!("plugin `{}` exited with {}", path.display(), status)));
+    }
+    let response: Response = serde_json::from_str(&stdout)
+        .map_err(|err| with_stderr(format!("plugin `{}` returned invalid JSON: {}", path.display(), err)))?;
+    groups(path, response)
+}
+
+/// The stat groups of the `response` of the plugin at `path`.
+fn groups(path: &Path, response: Response) -> Result<Vec<StatGroup>, ModuleError> {
+    if response.protocol != PROTOCOL_VERSION {
+        return Err(ModuleError::new(format!(
+            "plugin `{}` speaks protocol version {}, expected {}",
+            path.display(),
+            response.protocol,
+            PROTOCOL_VERSION
+        )));
+    }
+    let mut groups: Vec<StatGroup> = Vec::new();
+    if !response.stats.is_empty() {
+        groups.push(StatGroup::untitled(convert_stats(response.stats)?));
+    }
+    for group in response.groups {
+        groups.push(StatGroup {
+            title: group.title,
+            stats: convert_stats(group.stats)?,
+        });
+    }
+    Ok(groups)
+}
+
+fn read_in_background(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut output = String::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_string(&mut output);
+        }
+        output
+    })
+}
+
+fn convert_stats(stats: Vec<StatResponse>) -> Result<Vec<Stat>, ModuleError> {
+    stats
+        .into_iter()
+        .map(|stat| Ok(Stat::new(&stat.name, convert_value(&stat.name, stat.value, stat.unit.as_deref())?)))
+        .collect()
+}
+
+fn convert_value(name: &str, value: serde_json::Value, unit: Option<&str>) -> Result<StatValue, ModuleError> {
+    use serde_json::Value;
+    let number = match value {
+        Value::Null => return Ok(StatValue::Missing),
+        Value::Bool(value) => return Ok(StatValue::Bool(value)),
+        Value::String(text) => return Ok(StatValue::Text(text)),
+        Value::Number(number) => number,
+        Value::Array(_) | Value::Object(_) => {
+            return Err(ModuleError::new(format!(
+                "stat `{}`: value must be a string, number, boolean or null",
+                name
+            )))
+        }
+    };
+    let float = number.as_f64().unwrap_or_default();
+    Ok(match (unit, number.as_u64()) {
+        (None, Some(count)) => StatValue::Count(count),
+        (None, None) => StatValue::Number(float),
+        (Some("bytes"), Some(bytes)) => StatValue::Bytes(bytes),
+        (Some("percent"), _) => StatValue::Percent(float),
+        (Some("MHz"), Some(mhz)) => StatValue::Frequency(mhz),
+        (Some("seconds"), _) => StatValue::Duration(Duration::try_from_secs_f64(float).map_err(|_| {
+            ModuleError::new(format!("stat `{}`: {} is not a valid number of seconds", name, number))
+        })?),
+        (Some("Wh"), _) => StatValue::Energy(float),
+        (Some("W"), _) => StatValue::Power(float),
+        (Some(unit), _) => {
+            return Err(ModuleError::new(format!(
+                "stat `{}`: unsupported unit `{}` for value {}",
+                name, unit, number
+            )))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(stdout: &str) -> Result<Vec<StatGroup>, String> {
+        let response: Response = serde_json::from_str(stdout).map_err(|err| err.to_string())?;
+        groups(Path::new("plugin"), response).map_err(|err| err.message)
+    }
+
+    fn value(value: serde_json::Value, unit: Option<&str>) -> Result<StatValue, String> {
+        convert_value("stat", value, unit).map_err(|err| err.message)
+    }
+
+    #[test]
+    fn values_take_their_unit() {
+        use serde_json::json;
+        assert_eq!(value(json!(3), None), Ok(StatValue::Count(3)));
+        assert_eq!(value(json!(-1.5), None), Ok(StatValue::Number(-1.5)));
+        assert_eq!(value(json!(1024), Some("bytes")), Ok(StatValue::Bytes(1024)));
+        assert_eq!(value(json!(40), Some("percent")), Ok(StatValue::Percent(40.0)));
+        assert_eq!(value(json!(3400), Some("MHz")), Ok(StatValue::Frequency(3400)));
+        assert_eq!(value(json!(90.5), Some("seconds")), Ok(StatValue::Duration(Duration::from_secs_f64(90.5))));
+        assert_eq!(value(json!(12.5), Some("Wh")), Ok(StatValue::Energy(12.5)));
+        assert_eq!(value(json!(-4), Some("W")), Ok(StatValue::Power(-4.0)));
+        assert_eq!(value(json!("text"), Some("bytes")), Ok(StatValue::Text("text".to_string())));
+        assert_eq!(value(json!(true), None), Ok(StatValue::Bool(true)));
+        assert_eq!(value(json!(null), None), Ok(StatValue::Missing));
+    }
+
+    #[test]
+    fn values_out_of_range_are_errors() {
+        use serde_json::json;
+        assert_eq!(value(json!(1e30), Some("seconds")), Err("stat `stat`: 1e30 is not a valid number of seconds".to_string()));
+        assert_eq!(value(json!(-1), Some("seconds")), Err("stat `stat`: -1 is not a valid number of seconds".to_string()));
+        assert_eq!(value(json!(1.5), Some("bytes")), Err("stat `stat`: unsupported unit `bytes` for value 1.5".to_string()));
+        assert_eq!(value(json!(1), Some("K")), Err("stat `stat`: unsupported unit `K` for value 1".to_string()));
+        assert!(value(json!([1]), None).is_err());
+    }
+
+    #[test]
+    fn responses_with_and_without_groups() {
+        let groups = decode(r#"{"protocol": 1, "stats": [{"name": "pods", "value": 12}]}"#).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].title, None);
+        assert_eq!(groups[0].stats[0].value, StatValue::Count(12));
+        let groups = decode(
+            r#"{"protocol": 1, "groups": [{"title": "prod", "stats": []}, {"stats": [{"name": "up", "value": 90, "unit": "seconds"}]}]}"#,
+        )
+        .unwrap();
+        assert_eq!(groups[0].title.as_deref(), Some("prod"));
+        assert_eq!(groups[1].stats[0].value, StatValue::Duration(Duration::from_secs(90)));
+    }
+
+    #[test]
+    fn bad_responses_are_errors() {
+        assert!(decode("pods: 12").is_err());
+        assert!(decode(r#"{"protocol": 1, "stats": [{"value": 12}]}"#).is_err());
+        assert!(decode(r#"{"protocol": 1, "extra": true}"#).is_err());
+        assert_eq!(
+            decode(r#"{"protocol": 2, "stats": []}"#),
+            Err("plugin `plugin` speaks protocol version 2, expected 1".to_string())
+        );
+        assert_eq!(
+            decode(r#"{"protocol": 1, "stats": [{"name": "up", "value": 1e30, "unit": "seconds"}]}"#),
+            Err("stat `up`: 1e30 is not a valid number of seconds".to_string())
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use std::fmt;
+use std::time::Duration;
+
+/// A single value reported by a module. Values keep their numeric form
+/// until they are rendered; all human formatting lives in the `Display`
+/// implementation below.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatValue {
+    /// A size in bytes.
+    Bytes(u64),
+    /// A percentage between 0 and 100.
+    Percent(f64),
+    /// A clock frequency in MHz.
+    Frequency(u64),
+    Duration(Duration),
+    Count(u64),
+    /// A number without a unit.
+    Number(f64),
+    /// Energy in watt-hours.
+    Energy(f64),
+    /// Power in watts.
+    Power(f64),
+    Bool(bool),
+    Text(String),
+    /// The stat is known but this machine does not report a value for it.
+    Missing,
+    /// The module does not know the requested stat.
+    Unsupported,
+}
+
+impl StatValue {
+    /// The value before formatting: a number in [`unit`](Self::unit), a
+    /// string, a boolean, or null when there is none.
+    pub fn raw(&self) -> serde_json::Value {
+        match self {
+            StatValue::Bytes(value) | StatValue::Frequency(value) | StatValue::Count(value) => (*value).into(),
+            StatValue::Percent(value)
+            | StatValue::Number(value)
+            | StatValue::Energy(value)
+            | StatValue::Power(value) => (*value).into(),
+            StatValue::Duration(duration) => duration.as_secs_f64().into(),
+            StatValue::Bool(value) => (*value).into(),
+            StatValue::Text(text) => text.as_str().into(),
+            StatValue::Missing | StatValue::Unsupported => serde_json::Value::Null,
+        }
+    }
+    /// Unit of the raw value, written like plugins write it (see
+    /// [`plugin`](super::plugin)). Empty for values without one.
+    pub fn unit(&self) -> &'static str {
+        match self {
+            StatValue::Bytes(_) => "bytes",
+            StatValue::Percent(_) => "percent",
+            StatValue::Frequency(_) => "MHz",
+            StatValue::Duration(_) => "seconds",
+            StatValue::Energy(_) => "Wh",
+            StatValue::Power(_) => "W",
+            _ => "",
+        }
+    }
+}
+
+/// A named value as requested in the module's `stats` list.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stat {
+    pub name: String,
+    pub value: StatValue,
+}
+
+impl Stat {
+    pub fn new(name: &str, value: StatValue) -> Stat {
+        Stat {
+            name: name.to_string(),
+            value,
+        }
+    }
+}
+
+/// A stat a module can report, as documented in the help output and the
+/// man page.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StatInfo {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Unit of the raw value, e.g. `bytes` or `percent`, empty for text,
+    /// flags and counts.
+    pub unit: &'static str,
+    /// What the value typically looks like in the output.
+    pub example: &'static str,
+}
+
+/// An ordered set of stats belonging to one instance of a module (one
+/// disk, one battery, ...). Modules with a single instance return one group
+/// without a title.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatGroup {
+    pub title: Option<String>,
+    pub stats: Vec<Stat>,
+}
+
+impl StatGroup {
+    pub fn untitled(stats: Vec<Stat>) -> StatGroup {
+        StatGroup { title: None, stats }
+    }
+}
+
+const BYTE_UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+
+impl fmt::Display for StatValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StatValue::Bytes(bytes) => {
+                let mut value = *bytes as f64;
+                let mut unit = 0;
+                while value >= 1024.0 && unit < BYTE_UNITS.len() - 1 {
+                    value /= 1024.0;
+                    unit += 1;
+                }
+                if unit == 0 {
+                    write!(f, "{} {}", bytes, BYTE_UNITS[unit])
+                } else {
+                    write!(f, "{:.1} {}", value, BYTE_UNITS[unit])
+                }
+            }
+            StatValue::Percent(percent) => write!(f, "{:.1}%", percent),
+            StatValue::Frequency(mhz) => write!(f, "{} MHz", mhz),
+            StatValue::Duration(duration) => {
+                let hours = duration.as_secs() / 3600;
+                let minutes = (duration.as_secs() % 3600) / 60;
+                write!(f, "{:02}:{:02}", hours, minutes)
+            }
+            StatValue::Count(count) => write!(f, "{}", count),
+            StatValue::Number(number) => write!(f, "{}", number),
+            StatValue::Energy(wh) => write!(f, "{:.1} Wh", wh),
+            StatValue::Power(watts) => write!(f, "{:.1} W", watts),
+            StatValue::Bool(value) => write!(f, "{}", value),
+            StatValue::Text(text) => f.write_str(text),
+            StatValue::Missing => f.write_str("-"),
+            StatValue::Unsupported => f.write_str("unknown"),
+        }
+    }
+}
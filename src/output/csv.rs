@@ -0,0 +1,71 @@
+//! `--format csv`: one row per stat, as described in RFC 4180.
+use crate::output::Report;
+use serde_json::Value;
+
+const HEADER: &[&str] = &["module", "group", "stat", "value", "unit"];
+
+/// The rows of `report` after a header row, separated by CRLF.
+pub fn render(report: &Report) -> String {
+    let mut csv = row(HEADER.iter().copied());
+    for module in &report.modules {
+        for group in &module.groups {
+            for stat in &group.stats {
+                let value = match &stat.value {
+                    Value::Null => String::new(),
+                    Value::String(text) => text.clone(),
+                    value => value.to_string(),
+                };
+                csv.push_str(&row([
+                    module.name.as_str(),
+                    group.title.as_deref().unwrap_or_default(),
+                    stat.name.as_str(),
+                    value.as_str(),
+                    stat.unit.unwrap_or_default(),
+                ]));
+            }
+        }
+    }
+    csv
+}
+
+fn row<'a>(fields: impl IntoIterator<Item = &'a str>) -> String {
+    let fields: Vec<String> = fields.into_iter().map(field).collect();
+    fields.join(",") + "\r\n"
+}
+
+/// Quotes a field if it contains a separator, a quote or a line break,
+/// doubling the quotes inside it.
+fn field(text: &str) -> String {
+    match text.contains([',', '"', '\r', '\n']) {
+        true => format!("\"{}\"", text.replace('"', "\"\"")),
+        false => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::tests::report;
+
+    #[test]
+    fn rows_follow_the_header() {
+        let csv = render(&report());
+        let rows: Vec<&str> = csv.split_terminator("\r\n").collect();
+        assert_eq!(rows[0], "module,group,stat,value,unit");
+        assert_eq!(rows[1], "CPU,,name,\"Example, \"\"Fast\"\" CPU\",");
+        assert_eq!(rows[2], "CPU,,frequency,3400,MHz");
+        assert_eq!(rows[4], "CPU,,temperature,,");
+        assert_eq!(rows[5], "Disks,sda1,mount_point,/,");
+        assert_eq!(rows[8], "Disks,sda1,removable,false,");
+        assert_eq!(rows.len(), 1 + 4 + 8);
+        assert!(csv.ends_with("\r\n"));
+    }
+
+    #[test]
+    fn fields_are_quoted_when_needed() {
+        assert_eq!(field("plain text"), "plain text");
+        assert_eq!(field("a,b"), "\"a,b\"");
+        assert_eq!(field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(field("two\nlines"), "\"two\nlines\"");
+    }
+}
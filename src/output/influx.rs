@@ -0,0 +1,105 @@
+//! `--format influx`: InfluxDB line protocol.
+//!
+//! One line per group of a module, e.g.
+//! `ff_disk,mount_point=/,file_system=ext4 total=512000000000i,used=1200000i 1700000000000000000`:
+//! the measurement is named after the module, the label stats of the group
+//! are its tags (see [`ModuleInfo::labels`]) and its numeric stats the
+//! fields, in their own unit. A stat named `time`, which InfluxDB reserves,
+//! gets its unit appended, e.g. `time_seconds`. NaN and infinite values,
+//! which the line protocol cannot express, are left out. Every line has the
+//! same timestamp, in nanoseconds.
+//!
+//! [`ModuleInfo::labels`]: crate::modules::ModuleInfo::labels
+use crate::output::{series, Report, StatReport};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub fn render(report: &Report) -> String {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_nanos());
+    let mut text = String::new();
+    for series in series(report) {
+        let mut line = escape(&format!("ff_{}", series.metric), ", ");
+        for (name, value) in series.labels.iter().filter(|(_, value)| !value.is_empty()) {
+            line.push_str(&format!(",{}={}", escape(name, ",= "), escape(value, ",= ")));
+        }
+        let fields: Vec<String> = series.stats.iter().filter_map(|(stat, value)| field(stat, *value)).collect();
+        // a line needs at least one field
+        if fields.is_empty() {
+            continue;
+        }
+        text.push_str(&format!("{} {} {}\n", line, fields.join(","), timestamp));
+    }
+    text
+}
+
+/// `key=value` for `stat`, or `None` if `value` is NaN or infinite.
+fn field(stat: &StatReport, value: f64) -> Option<String> {
+    let value = match stat.value.as_u64() {
+        Some(integer) => format!("{}i", integer),
+        None if value.is_finite() => value.to_string(),
+        None => return None,
+    };
+    Some(format!("{}={}", field_key(&stat.name, stat.unit), value))
+}
+
+/// `name` as a field key, escaped and renamed if InfluxDB reserves it.
+fn field_key(name: &str, unit: Option<&str>) -> String {
+    match name {
+        "time" => format!("time_{}", escape(unit.unwrap_or("value"), ",= ")),
+        name => escape(name, ",= "),
+    }
+}
+
+/// `text` with a backslash before each of `special`.
+fn escape(text: &str, special: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for char in text.chars() {
+        if special.contains(char) {
+            escaped.push('\\');
+        }
+        escaped.push(char);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::tests::report;
+    use serde_json::json;
+
+    #[test]
+    fn one_line_per_group() {
+        let text = render(&report());
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        let timestamp = lines[0].rsplit(' ').next().unwrap();
+        assert!(lines.iter().all(|line| line.ends_with(timestamp)));
+        assert!(lines[0].starts_with("ff_cpu frequency=3400i,usage=12.5 "));
+        assert!(lines[2].starts_with("ff_disk,mount_point=/mnt/my\\ data,file_system=ext4 total=2048i "));
+    }
+
+    #[test]
+    fn non_finite_fields_are_left_out() {
+        let stat = |value: serde_json::Value| StatReport {
+            name: "load".to_string(),
+            value,
+            unit: None,
+            text: String::new(),
+        };
+        assert_eq!(field(&stat(json!(1.5)), 1.5), Some("load=1.5".to_string()));
+        assert_eq!(field(&stat(json!(7)), 7.0), Some("load=7i".to_string()));
+        for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert_eq!(field(&stat(json!(null)), value), None);
+        }
+    }
+
+    #[test]
+    fn field_keys_are_escaped_and_never_time() {
+        assert_eq!(field_key("time", Some("seconds")), "time_seconds");
+        assert_eq!(field_key("time", None), "time_value");
+        assert_eq!(field_key("a,b=c d", None), "a\\,b\\=c\\ d");
+        assert_eq!(field_key("timeout", Some("seconds")), "timeout");
+    }
+}
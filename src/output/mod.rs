@@ -0,0 +1,396 @@
+//! Machine readable output, chosen with `--format`.
+//!
+//! Instead of the coloured terminal layout the collected stats are turned
+//! into a [`Report`], which does not depend on the theme or the terminal, and
+//! written in the requested format. The layout of the report is stable:
+//! modules in config order, each with its status and groups, and every
+//! stat with its raw value, the unit of that value and the text the
+//! terminal output would show. CSV flattens it into one row per stat, Prometheus and
+//! InfluxDB keep the numeric stats only.
+mod csv;
+mod influx;
+mod prometheus;
+pub mod statsd;
+
+use crate::modules::stat::StatGroup;
+use crate::modules::{self, Collected, Job, ModuleInfo, Source};
+use serde::Serialize;
+use serde_json::Value;
+
+/// Version of the [`Report`] layout, raised when a field changes meaning or
+/// goes away.
+pub const REPORT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// The coloured terminal layout.
+    #[default]
+    Text,
+    Json,
+    Yaml,
+    Toml,
+    Csv,
+    Prometheus,
+    Influx,
+}
+
+/// The formats `--format` accepts, as they are written there.
+pub const FORMATS: &[&str] = &["text", "json", "yaml", "toml", "csv", "prometheus", "influx"];
+
+impl OutputFormat {
+    pub fn parse(name: &str) -> Option<OutputFormat> {
+        match name.to_ascii_lowercase().as_str() {
+            "text" => Some(OutputFormat::Text),
+            "json" => Some(OutputFormat::Json),
+            "yaml" | "yml" => Some(OutputFormat::Yaml),
+            "toml" => Some(OutputFormat::Toml),
+            "csv" => Some(OutputFormat::Csv),
+            "prometheus" | "openmetrics" => Some(OutputFormat::Prometheus),
+            "influx" | "influxdb" => Some(OutputFormat::Influx),
+            _ => None,
+        }
+    }
+    /// Whether the format labels stats with the label stats of their module
+    /// (see [`ModuleInfo::labels`]), which are then collected even if the
+    /// config does not list them.
+    ///
+    /// [`ModuleInfo::labels`]: crate::modules::ModuleInfo::labels
+    pub fn labelled(&self) -> bool {
+        matches!(self, OutputFormat::Prometheus | OutputFormat::Influx)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub version: u32,
+    pub modules: Vec<ModuleReport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ModuleReport {
+    pub name: String,
+    /// `ok`, `unavailable`, `failed` or `timed_out`.
+    pub status: &'static str,
+    /// Why the module has no stats, unless its status is `ok`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub groups: Vec<GroupReport>,
+    /// Whether the module is a plugin, which may be named like a built-in
+    /// module but has none of its metadata.
+    #[serde(skip)]
+    pub plugin: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GroupReport {
+    /// The disk, battery, ... the stats belong to. Modules with a single
+    /// group have none.
+    pub title: Option<String>,
+    pub stats: Vec<StatReport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StatReport {
+    pub name: String,
+    pub value: Value,
+    /// Unit of `value`, e.g. `bytes`, or null.
+    pub unit: Option<&'static str>,
+    /// `value` as the terminal output shows it, e.g. `12.3 GB`.
+    pub text: String,
+}
+
+impl Report {
+    /// The report for every collected module and what it produced.
+    pub fn new<'a>(modules: impl IntoIterator<Item = (&'a Job, &'a Collected)>) -> Report {
+        Report {
+            version: REPORT_VERSION,
+            modules: modules
+                .into_iter()
+                .map(|(job, collected)| ModuleReport::new(job, collected))
+                .collect(),
+        }
+    }
+}
+
+impl ModuleReport {
+    fn new(job: &Job, collected: &Collected) -> ModuleReport {
+        let (status, error, groups) = match collected {
+            Collected::Stats(groups) => ("ok", None, groups.iter().map(GroupReport::new).collect()),
+            Collected::Unavailable(err) => ("unavailable", Some(err.to_string()), Vec::new()),
+            Collected::Failed(err) => ("failed", Some(err.to_string()), Vec::new()),
+            Collected::TimedOut(timeout) => (
+                "timed_out",
+                Some(format!("timed out after {} ms", timeout.as_millis())),
+                Vec::new(),
+            ),
+        };
+        ModuleReport {
+            name: job.name.clone(),
+            status,
+            error,
+            groups,
+            plugin: matches!(job.source, Source::Plugin(_)),
+        }
+    }
+}
+
+impl GroupReport {
+    fn new(group: &StatGroup) -> GroupReport {
+        GroupReport {
+            title: group.title.clone(),
+            stats: group
+                .stats
+                .iter()
+                .map(|stat| StatReport {
+                    name: stat.name.clone(),
+                    value: stat.value.raw(),
+                    unit: Some(stat.value.unit()).filter(|unit| !unit.is_empty()),
+                    text: stat.value.to_string(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// `report` written in `format`, or `None` for [`OutputFormat::Text`],
+/// which the caller lays out for the terminal.
+pub fn render(report: &Report, format: OutputFormat) -> Option<String> {
+    match format {
+        OutputFormat::Text => None,
+        OutputFormat::Json => Some(serde_json::to_string_pretty(report).expect("reports always serialize")),
+        OutputFormat::Yaml => Some(serde_yaml::to_string(report).expect("reports always serialize")),
+        OutputFormat::Toml => {
+            let report = serde_json::to_value(report).expect("reports always serialize");
+            Some(toml::to_string(&without_nulls(report)).expect("reports without nulls serialize to TOML"))
+        }
+        OutputFormat::Csv => Some(csv::render(report)),
+        OutputFormat::Prometheus => Some(prometheus::render(report)),
+        OutputFormat::Influx => Some(influx::render(report)),
+    }
+}
+
+/// `value` without null values, which TOML cannot express. A missing key
+/// means the same to readers of the report.
+fn without_nulls(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| (key, without_nulls(value)))
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(values.into_iter().map(without_nulls).collect()),
+        value => value,
+    }
+}
+
+/// The numeric stats of one group of a module, for the formats that carry
+/// numbers only.
+struct Series<'a> {
+    module: &'a ModuleReport,
+    /// `None` for plugins.
+    info: Option<&'static ModuleInfo>,
+    /// Name of the module in metric names, see [`ModuleInfo::metric`].
+    metric: String,
+    /// The label stats of the group with their values (see
+    /// [`ModuleInfo::labels`]), or the title of a plugin group as `group`.
+    labels: Vec<(&'static str, String)>,
+    stats: Vec<(&'a StatReport, f64)>,
+}
+
+/// Every group of `report` with numeric stats, in order.
+fn series(report: &Report) -> Vec<Series<'_>> {
+    let mut series = Vec::new();
+    for module in &report.modules {
+        let info = match module.plugin {
+            true => None,
+            false => modules::find(&module.name),
+        };
+        let metric = info.map_or_else(|| sanitize(&module.name.to_lowercase()), |info| info.metric.to_string());
+        let label_stats = info.map_or(&[][..], |info| info.labels);
+        for (index, group) in module.groups.iter().enumerate() {
+            let mut labels = Vec::new();
+            for &label in label_stats {
+                if label == "index" {
+                    labels.push((label, index.to_string()));
+                } else if let Some(stat) = group.stats.iter().find(|stat| stat.name == label && !stat.value.is_null()) {
+                    labels.push((label, stat.text.clone()));
+                }
+            }
+            if let (None, Some(title)) = (info, &group.title) {
+                labels.push(("group", title.clone()));
+            }
+            let stats: Vec<(&StatReport, f64)> = group
+                .stats
+                .iter()
+                .filter(|stat| !label_stats.contains(&stat.name.as_str()))
+                .filter_map(|stat| Some((stat, stat.value.as_f64()?)))
+                .collect();
+            if !stats.is_empty() {
+                series.push(Series {
+                    module,
+                    info,
+                    metric: metric.clone(),
+                    labels,
+                    stats,
+                });
+            }
+        }
+    }
+    series
+}
+
+/// `name` with every character but ASCII letters and digits replaced by
+/// `_`, as metric names allow.
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|char| if char.is_ascii_alphanumeric() { char } else { '_' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::module_trait::ModuleError;
+    use crate::modules::stat::{Stat, StatValue};
+    use serde_json::json;
+    use std::time::Duration;
+
+    /// A CPU, two disks, a failed plugin and a module that timed out.
+    pub(super) fn report() -> Report {
+        let disk = |title: &str, mount_point: &str, total: u64| StatGroup {
+            title: Some(title.to_string()),
+            stats: vec![
+                Stat::new("mount_point", StatValue::Text(mount_point.to_string())),
+                Stat::new("file_system", StatValue::Text("ext4".to_string())),
+                Stat::new("total", StatValue::Bytes(total)),
+                Stat::new("removable", StatValue::Bool(false)),
+            ],
+        };
+        let collected = [
+            (
+                "CPU",
+                Collected::Stats(vec![StatGroup::untitled(vec![
+                    Stat::new("name", StatValue::Text("Example, \"Fast\" CPU".to_string())),
+                    Stat::new("frequency", StatValue::Frequency(3400)),
+                    Stat::new("usage", StatValue::Percent(12.5)),
+                    Stat::new("temperature", StatValue::Missing),
+                ])]),
+            ),
+            ("Disks", Collected::Stats(vec![disk("sda1", "/", 1024), disk("sdb1", "/mnt/my data", 2048)])),
+            ("My Plugin", Collected::Failed(ModuleError::new("exited with status 1"))),
+            ("Battery", Collected::TimedOut(Duration::from_millis(500))),
+        ];
+        let jobs: Vec<(Job, Collected)> = collected
+            .into_iter()
+            .map(|(name, collected)| {
+                let source = match modules::find(name) {
+                    Some(info) => Source::Builtin(info),
+                    None => Source::Plugin(name.into()),
+                };
+                (job(name, source), collected)
+            })
+            .collect();
+        Report::new(jobs.iter().map(|(job, collected)| (job, collected)))
+    }
+
+    fn job(name: &str, source: Source) -> Job {
+        Job {
+            name: name.to_string(),
+            source,
+            stats: Vec::new(),
+            timeout: Duration::from_secs(1),
+        }
+    }
+
+    #[test]
+    fn reports_keep_status_raw_value_unit_and_text() {
+        let report = serde_json::to_value(report()).unwrap();
+        assert_eq!(report["version"], REPORT_VERSION);
+        assert_eq!(
+            report["modules"][0],
+            json!({
+                "name": "CPU",
+                "status": "ok",
+                "groups": [{
+                    "title": null,
+                    "stats": [
+                        { "name": "name", "value": "Example, \"Fast\" CPU", "unit": null, "text": "Example, \"Fast\" CPU" },
+                        { "name": "frequency", "value": 3400, "unit": "MHz", "text": "3400 MHz" },
+                        { "name": "usage", "value": 12.5, "unit": "percent", "text": "12.5%" },
+                        { "name": "temperature", "value": null, "unit": null, "text": "-" }
+                    ]
+                }]
+            })
+        );
+        assert_eq!(
+            report["modules"][2],
+            json!({ "name": "My Plugin", "status": "failed", "error": "exited with status 1", "groups": [] })
+        );
+        assert_eq!(report["modules"][3]["status"], "timed_out");
+        assert_eq!(report["modules"][3]["error"], "timed out after 500 ms");
+    }
+
+    #[test]
+    fn toml_leaves_out_nulls() {
+        let toml = render(&report(), OutputFormat::Toml).unwrap();
+        let parsed: toml::Value = toml::from_str(&toml).unwrap();
+        let stats = parsed["modules"][0]["groups"][0]["stats"].as_array().unwrap();
+        assert_eq!(stats[3].get("value"), None);
+        assert_eq!(stats[3]["text"].as_str(), Some("-"));
+        assert!(parsed["modules"][0]["groups"][0].get("title").is_none());
+    }
+
+    #[test]
+    fn structured_formats_agree() {
+        let report = report();
+        let json: Value = serde_json::from_str(&render(&report, OutputFormat::Json).unwrap()).unwrap();
+        let yaml: Value = serde_yaml::from_str(&render(&report, OutputFormat::Yaml).unwrap()).unwrap();
+        assert_eq!(json, yaml);
+        assert_eq!(render(&report, OutputFormat::Text), None);
+    }
+
+    #[test]
+    fn series_keep_numeric_stats_and_labels() {
+        let report = report();
+        let series = series(&report);
+        assert_eq!(series.len(), 3);
+        assert_eq!(series[0].metric, "cpu");
+        let names: Vec<_> = series[0].stats.iter().map(|(stat, value)| (stat.name.as_str(), *value)).collect();
+        assert_eq!(names, [("frequency", 3400.0), ("usage", 12.5)]);
+        assert_eq!(series[2].metric, "disk");
+        assert_eq!(
+            series[2].labels,
+            [("mount_point", "/mnt/my data".to_string()), ("file_system", "ext4".to_string())]
+        );
+        assert_eq!(series[2].stats.len(), 1);
+    }
+
+    #[test]
+    fn plugins_named_like_built_in_modules_keep_their_own_metadata() {
+        let job = job("Disks", Source::Plugin("/usr/bin/ff-disks".into()));
+        let collected = Collected::Stats(vec![StatGroup {
+            title: Some("nas".to_string()),
+            stats: vec![
+                Stat::new("mount_point", StatValue::Number(2.0)),
+                Stat::new("total", StatValue::Power(3.5)),
+            ],
+        }]);
+        let report = Report::new([(&job, &collected)]);
+        let series = series(&report);
+        assert!(series[0].info.is_none());
+        assert_eq!(series[0].metric, "disks");
+        assert_eq!(series[0].labels, [("group", "nas".to_string())]);
+        let stats: Vec<_> = series[0].stats.iter().map(|(stat, _)| (stat.name.as_str(), stat.unit)).collect();
+        assert_eq!(stats, [("mount_point", None), ("total", Some("W"))]);
+    }
+
+    #[test]
+    fn formats_parse_case_insensitively() {
+        for name in FORMATS {
+            assert!(OutputFormat::parse(name).is_some(), "{}", name);
+        }
+        assert_eq!(OutputFormat::parse("OpenMetrics"), Some(OutputFormat::Prometheus));
+        assert_eq!(OutputFormat::parse("xml"), None);
+    }
+}
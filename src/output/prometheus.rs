@@ -0,0 +1,166 @@
+//! `--format prometheus`: the text exposition format read by Prometheus and
+//! by the textfile collector of node_exporter.
+//!
+//! Every numeric stat becomes a gauge named `ff_<module>_<stat>_<unit>` in
+//! the base unit Prometheus expects, e.g. `ff_cpu_frequency_hertz` or
+//! `ff_cpu_usage_ratio` for a percentage, labelled with the stats that tell
+//! the groups of its module apart (see [`ModuleInfo::labels`]). A stat named
+//! after its unit, like `percent` of Memory, is named after the base unit
+//! instead: `ff_memory_ratio`. Text, flags and missing values are left out.
+use crate::modules::ModuleInfo;
+use crate::output::{sanitize, series, ModuleReport, Report, StatReport};
+
+struct Metric {
+    name: String,
+    help: String,
+    samples: Vec<String>,
+}
+
+/// The metrics of `report`, each with its HELP and TYPE line.
+pub fn render(report: &Report) -> String {
+    let mut metrics: Vec<Metric> = Vec::new();
+    for series in series(report) {
+        let labels = format_labels(&series.labels);
+        for (stat, value) in series.stats {
+            let (unit, scale) = base_unit(stat.unit.unwrap_or_default());
+            let mut name = match stat.unit == Some(stat.name.as_str()) {
+                true => format!("ff_{}_{}", series.metric, unit),
+                false => format!("ff_{}_{}", series.metric, sanitize(&stat.name)),
+            };
+            if !unit.is_empty() && !name.ends_with(unit) {
+                name = format!("{}_{}", name, unit);
+            }
+            let sample = format!("{}{} {}", name, labels, format_value(value * scale));
+            match metrics.iter_mut().find(|metric| metric.name == name) {
+                Some(metric) => metric.samples.push(sample),
+                None => metrics.push(Metric {
+                    help: help(series.info, series.module, stat),
+                    name,
+                    samples: vec![sample],
+                }),
+            }
+        }
+    }
+    let mut text = String::new();
+    for metric in metrics {
+        text.push_str(&format!("# HELP {} {}\n", metric.name, metric.help));
+        text.push_str(&format!("# TYPE {} gauge\n", metric.name));
+        for sample in metric.samples {
+            text.push_str(&sample);
+            text.push('\n');
+        }
+    }
+    text
+}
+
+/// The suffix of the base unit for a stat unit and the factor that
+/// converts a value into it.
+fn base_unit(unit: &str) -> (&'static str, f64) {
+    match unit {
+        "bytes" => ("bytes", 1.0),
+        "percent" => ("ratio", 0.01),
+        "MHz" => ("hertz", 1e6),
+        "seconds" => ("seconds", 1.0),
+        "Wh" => ("joules", 3600.0),
+        "W" => ("watts", 1.0),
+        _ => ("", 1.0),
+    }
+}
+
+/// `value` as the exposition format writes it, which spells the values
+/// without digits `+Inf`, `-Inf` and `NaN`.
+fn format_value(value: f64) -> String {
+    match value {
+        value if value.is_nan() => "NaN".to_string(),
+        f64::INFINITY => "+Inf".to_string(),
+        f64::NEG_INFINITY => "-Inf".to_string(),
+        value => value.to_string(),
+    }
+}
+
+fn help(info: Option<&ModuleInfo>, module: &ModuleReport, stat: &StatReport) -> String {
+    let help = match info.and_then(|info| info.stat(&stat.name)) {
+        Some(stat) => stat.description.to_string(),
+        None => format!("`{}` of the {} module.", stat.name, module.name),
+    };
+    help.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+/// `{name="value",...}`, or nothing without labels.
+fn format_labels(labels: &[(&str, String)]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+    let labels: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+            format!("{}=\"{}\"", name, value)
+        })
+        .collect();
+    format!("{{{}}}", labels.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::tests::report;
+
+    #[test]
+    fn gauges_in_base_units() {
+        let text = render(&report());
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines[..6],
+            [
+                "# HELP ff_cpu_frequency_hertz Current clock frequency of the first core.",
+                "# TYPE ff_cpu_frequency_hertz gauge",
+                "ff_cpu_frequency_hertz 3400000000",
+                "# HELP ff_cpu_usage_ratio Load over all cores, measured over a short interval.",
+                "# TYPE ff_cpu_usage_ratio gauge",
+                "ff_cpu_usage_ratio 0.125",
+            ]
+        );
+        assert!(text.contains(
+            "# TYPE ff_disk_total_bytes gauge\n\
+             ff_disk_total_bytes{mount_point=\"/\",file_system=\"ext4\"} 1024\n\
+             ff_disk_total_bytes{mount_point=\"/mnt/my data\",file_system=\"ext4\"} 2048\n"
+        ));
+        assert!(!text.contains("removable"));
+        assert!(!text.contains("Plugin"));
+    }
+
+    #[test]
+    fn stats_named_after_their_unit() {
+        let stat = StatReport {
+            name: "percent".to_string(),
+            value: 40.0.into(),
+            unit: Some("percent"),
+            text: "40.0%".to_string(),
+        };
+        let report = Report {
+            version: 1,
+            modules: vec![ModuleReport {
+                name: "Memory".to_string(),
+                status: "ok",
+                error: None,
+                groups: vec![crate::output::GroupReport { title: None, stats: vec![stat] }],
+                plugin: false,
+            }],
+        };
+        assert!(render(&report).ends_with("\nff_memory_ratio 0.4\n"));
+    }
+
+    #[test]
+    fn values_and_labels_are_escaped() {
+        assert_eq!(format_value(f64::INFINITY), "+Inf");
+        assert_eq!(format_value(f64::NEG_INFINITY), "-Inf");
+        assert_eq!(format_value(f64::NAN), "NaN");
+        assert_eq!(format_value(-1.5), "-1.5");
+        assert_eq!(format_labels(&[]), "");
+        assert_eq!(
+            format_labels(&[("group", "a \"b\" \\ c\nd".to_string())]),
+            "{group=\"a \\\"b\\\" \\\\ c\\nd\"}"
+        );
+    }
+}
@@ -0,0 +1,187 @@
+//! `--push statsd://HOST:PORT`: the numeric stats as StatsD gauges, sent
+//! over UDP instead of being printed.
+//!
+//! Each stat is a gauge named `ff.<module>.<labels>.<stat>` in its own
+//! unit, where the labels are the values of the label stats of its group
+//! (see [`ModuleInfo::labels`]), e.g. `ff.disk.home.ext4.used` or
+//! `ff.battery.0.percent`. NaN and infinite values, which StatsD cannot
+//! read, are not sent. Gauges are packed into as few datagrams as fit a
+//! typical MTU.
+//!
+//! [`ModuleInfo::labels`]: crate::modules::ModuleInfo::labels
+use crate::error::Error;
+use crate::output::{sanitize, series, Report};
+use std::io;
+use std::net::{ToSocketAddrs, UdpSocket};
+
+const SCHEME: &str = "statsd://";
+const DEFAULT_PORT: u16 = 8125;
+/// Bytes of gauges per datagram, small enough not to be fragmented.
+const MAX_DATAGRAM: usize = 1432;
+
+/// The `HOST:PORT` a `statsd://HOST[:PORT]` URL points at, or `None` if it
+/// is not such a URL.
+pub fn address(url: &str) -> Option<String> {
+    let host = url.strip_prefix(SCHEME)?.trim_end_matches('/');
+    if host.is_empty() || host.contains('/') {
+        return None;
+    }
+    // `[::1]` is an IPv6 address without a port
+    match host.rsplit_once(':') {
+        Some((name, port)) if !host.ends_with(']') => {
+            (!name.is_empty() && port.parse::<u16>().is_ok()).then(|| host.to_string())
+        }
+        _ => Some(format!("{}:{}", host, DEFAULT_PORT)),
+    }
+}
+
+/// Sends the gauges of `report` to the StatsD server at `url`.
+pub fn push(report: &Report, url: &str) -> Result<(), Error> {
+    let failed = |source: io::Error| Error::Push {
+        target: url.to_string(),
+        source,
+    };
+    let address = address(url).unwrap_or_else(|| url.to_string());
+    let target = address
+        .to_socket_addrs()
+        .map_err(failed)?
+        .next()
+        .ok_or_else(|| failed(io::Error::new(io::ErrorKind::NotFound, "the host has no address")))?;
+    let local = if target.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+    let socket = UdpSocket::bind(local).map_err(failed)?;
+    for datagram in datagrams(&gauges(report)) {
+        socket.send_to(datagram.as_bytes(), target).map_err(failed)?;
+    }
+    Ok(())
+}
+
+/// One `name:value|g` line per stat.
+fn gauges(report: &Report) -> Vec<String> {
+    let mut gauges = Vec::new();
+    for series in series(report) {
+        let mut prefix = format!("ff.{}", series.metric);
+        for (_, value) in &series.labels {
+            let value = sanitize(value);
+            let value = value.trim_matches('_');
+            if !value.is_empty() {
+                prefix.push('.');
+                prefix.push_str(value);
+            }
+        }
+        for (stat, value) in series.stats {
+            gauges.extend(gauge(&format!("{}.{}", prefix, sanitize(&stat.name)), value));
+        }
+    }
+    gauges
+}
+
+/// The lines that set the gauge `name` to `value`, none if it is NaN or
+/// infinite.
+fn gauge(name: &str, value: f64) -> Vec<String> {
+    match value {
+        value if !value.is_finite() => Vec::new(),
+        // a gauge with a sign changes the current value instead of
+        // setting it, so a negative value needs a reset to 0 first
+        value if value < 0.0 => vec![format!("{}:0|g", name), format!("{}:{}|g", name, value)],
+        value => vec![format!("{}:{}|g", name, value)],
+    }
+}
+
+/// `gauges` joined by newlines into datagrams of at most [`MAX_DATAGRAM`]
+/// bytes, unless a single gauge is longer.
+fn datagrams(gauges: &[String]) -> Vec<String> {
+    let mut datagrams: Vec<String> = Vec::new();
+    for gauge in gauges {
+        match datagrams.last_mut() {
+            Some(datagram) if datagram.len() + 1 + gauge.len() <= MAX_DATAGRAM => {
+                datagram.push('\n');
+                datagram.push_str(gauge);
+            }
+            _ => datagrams.push(gauge.clone()),
+        }
+    }
+    datagrams
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::tests::report;
+    use crate::output::{GroupReport, ModuleReport, StatReport};
+
+    #[test]
+    fn urls_name_a_host_and_port() {
+        assert_eq!(address("statsd://localhost"), Some("localhost:8125".to_string()));
+        assert_eq!(address("statsd://localhost:9125/"), Some("localhost:9125".to_string()));
+        assert_eq!(address("statsd://[::1]"), Some("[::1]:8125".to_string()));
+        assert_eq!(address("statsd://[::1]:9125"), Some("[::1]:9125".to_string()));
+        assert_eq!(address("statsd://localhost:port"), None);
+        assert_eq!(address("statsd://host/path"), None);
+        assert_eq!(address("statsd://"), None);
+        assert_eq!(address("udp://localhost"), None);
+    }
+
+    #[test]
+    fn gauges_are_named_after_their_labels() {
+        assert_eq!(
+            gauges(&report()),
+            [
+                "ff.cpu.frequency:3400|g",
+                "ff.cpu.usage:12.5|g",
+                "ff.disk.ext4.total:1024|g",
+                "ff.disk.mnt_my_data.ext4.total:2048|g",
+            ]
+        );
+    }
+
+    #[test]
+    fn negative_gauges_are_reset_first() {
+        let stat = StatReport {
+            name: "power".to_string(),
+            value: (-4.5).into(),
+            unit: Some("W"),
+            text: "-4.5 W".to_string(),
+        };
+        let report = Report {
+            version: 1,
+            modules: vec![ModuleReport {
+                name: "My Plugin".to_string(),
+                status: "ok",
+                error: None,
+                groups: vec![GroupReport { title: Some("left".to_string()), stats: vec![stat] }],
+                plugin: true,
+            }],
+        };
+        assert_eq!(gauges(&report), ["ff.my_plugin.left.power:0|g", "ff.my_plugin.left.power:-4.5|g"]);
+    }
+
+    #[test]
+    fn non_finite_gauges_are_not_sent() {
+        assert!(gauge("ff.test.value", f64::NAN).is_empty());
+        assert!(gauge("ff.test.value", f64::INFINITY).is_empty());
+        assert!(gauge("ff.test.value", f64::NEG_INFINITY).is_empty());
+        assert_eq!(gauge("ff.test.value", 0.5), ["ff.test.value:0.5|g"]);
+    }
+
+    #[test]
+    fn datagrams_stay_small() {
+        let gauges: Vec<String> = (0..200).map(|index| format!("ff.test.stat_{}:{}|g", index, index)).collect();
+        let packed = datagrams(&gauges);
+        assert!(packed.len() > 1);
+        assert!(packed.iter().all(|datagram| datagram.len() <= MAX_DATAGRAM));
+        assert_eq!(packed.join("\n"), gauges.join("\n"));
+        let long = "x".repeat(MAX_DATAGRAM + 1);
+        assert_eq!(datagrams(&["a:1|g".to_string(), long.clone()]), ["a:1|g".to_string(), long]);
+    }
+
+    #[test]
+    fn pushes_over_udp() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        server.set_read_timeout(Some(std::time::Duration::from_secs(5))).unwrap();
+        let url = format!("statsd://{}", server.local_addr().unwrap());
+        push(&report(), &url).unwrap();
+        let mut buffer = [0; MAX_DATAGRAM];
+        let read = server.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..read], gauges(&report()).join("\n").as_bytes());
+    }
+}
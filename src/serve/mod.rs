@@ -0,0 +1,265 @@
+//! `ff serve`: a small HTTP server for Prometheus and other scrapers.
+//!
+//! | path       | response                                      |
+//! |------------|-----------------------------------------------|
+//! | `/metrics` | the stats as `--format prometheus` prints them |
+//! | `/json`    | the stats as `--format json` prints them       |
+//! | `/healthz` | `ok`, without collecting anything             |
+//!
+//! The modules are collected again for a scrape unless the last collection
+//! is younger than the cache interval, so that several scrapers at once do
+//! not multiply the load. Every connection is answered on its own thread,
+//! so a slow client does not hold up the others. SIGTERM and SIGINT stop
+//! the server once the requests it is answering are done.
+use crate::chalk::Chalk;
+use crate::error::Error;
+use crate::output::{self, OutputFormat, Report};
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:9108";
+/// Milliseconds a collection is reused for, unless `--cache` says otherwise.
+pub const DEFAULT_CACHE: u64 = 1000;
+
+/// How often the server checks for a signal while no request comes in.
+const POLL: Duration = Duration::from_millis(100);
+/// How long a client may take to send its request or read the response,
+/// which is also how long it can delay stopping the server.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(1);
+/// Requests with a longer head are refused.
+const MAX_HEAD: usize = 16 * 1024;
+
+/// Serves the reports of `collect` on `address` until the process is asked
+/// to stop.
+pub fn run(address: &str, cache: Duration, collect: impl FnMut() -> Report + Send) -> Result<(), Error> {
+    let failed = |source: io::Error| Error::Serve {
+        address: address.to_string(),
+        source,
+    };
+    let listener = TcpListener::bind(address).map_err(failed)?;
+    let stop = Arc::new(AtomicBool::new(false));
+    for signal in [signal_hook::consts::SIGTERM, signal_hook::consts::SIGINT] {
+        signal_hook::flag::register(signal, Arc::clone(&stop)).map_err(failed)?;
+    }
+    let local = listener.local_addr().map_err(failed)?;
+    note(&format!("serving metrics on http://{}/metrics", local));
+    serve(listener, cache, &stop, collect).map_err(failed)?;
+    note("stopping");
+    Ok(())
+}
+
+/// The last report and what collects the next one. Scrapers that arrive
+/// while a report is collected wait for it rather than collect their own.
+struct Cache<F> {
+    report: Option<(Instant, Report)>,
+    collect: F,
+}
+
+/// Answers the connections of `listener` until `stop` is set, then waits
+/// for the requests in progress.
+fn serve(
+    listener: TcpListener,
+    cache: Duration,
+    stop: &AtomicBool,
+    collect: impl FnMut() -> Report + Send,
+) -> io::Result<()> {
+    // accept does not return when a signal arrives, so it is polled
+    listener.set_nonblocking(true)?;
+    let cached = Mutex::new(Cache { report: None, collect });
+    thread::scope(|scope| {
+        while !stop.load(Ordering::Relaxed) {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    let cached = &cached;
+                    scope.spawn(move || {
+                        // a client that goes away is its own problem
+                        let _ = answer(stream, |path| {
+                            let mut cached = cached.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                            let Cache { report, collect } = &mut *cached;
+                            let expired = report.as_ref().is_none_or(|(collected, _)| collected.elapsed() >= cache);
+                            if expired {
+                                *report = Some((Instant::now(), collect()));
+                            }
+                            let report = &report.as_ref().expect("a report was collected").1;
+                            match path {
+                                "/metrics" => render(report, OutputFormat::Prometheus),
+                                _ => render(report, OutputFormat::Json) + "\n",
+                            }
+                        });
+                    });
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => thread::sleep(POLL),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                // e.g. too many open files, which may pass
+                Err(err) => {
+                    note(&format!("cannot accept a connection: {}", err));
+                    thread::sleep(POLL);
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn text(status: &'static str, body: impl Into<String>) -> Response {
+        Response {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: body.into(),
+        }
+    }
+}
+
+/// Reads one request from `stream` and answers it. `stats` renders the
+/// stats for `/metrics` or `/json`.
+fn answer(mut stream: TcpStream, stats: impl FnOnce(&str) -> String) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let head = read_head(&mut stream)?;
+    let mut request_line = head.lines().next().unwrap_or_default().split(' ');
+    let method = request_line.next().unwrap_or_default();
+    let target = request_line.next().unwrap_or_default();
+    let path = target.split('?').next().unwrap_or_default();
+    let response = if method != "GET" && method != "HEAD" {
+        Response::text("405 Method Not Allowed", "only GET and HEAD are supported\n")
+    } else {
+        match path {
+            "/metrics" | "/json" => Response {
+                status: "200 OK",
+                content_type: match path {
+                    "/metrics" => "text/plain; version=0.0.4; charset=utf-8",
+                    _ => "application/json",
+                },
+                body: stats(path),
+            },
+            "/healthz" => Response::text("200 OK", "ok\n"),
+            "/" => Response::text("200 OK", "ff\n\n/metrics  Prometheus metrics\n/json     the stats as JSON\n/healthz  health check\n"),
+            _ => Response::text("404 Not Found", format!("no page at {}\n", path)),
+        }
+    };
+    let mut allow = String::new();
+    if response.status.starts_with("405") {
+        allow.push_str("Allow: GET, HEAD\r\n");
+    }
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n",
+        response.status,
+        response.content_type,
+        response.body.len(),
+        allow
+    )?;
+    if method != "HEAD" {
+        stream.write_all(response.body.as_bytes())?;
+    }
+    stream.flush()
+}
+
+/// The request line and headers. The body of a request is never needed.
+fn read_head(stream: &mut TcpStream) -> io::Result<String> {
+    let mut head = Vec::new();
+    let mut buffer = [0; 1024];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+        if head.len() > MAX_HEAD {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "the request head is too long"));
+        }
+        match stream.read(&mut buffer)? {
+            0 => break,
+            read => head.extend_from_slice(&buffer[..read]),
+        }
+    }
+    Ok(String::from_utf8_lossy(&head).into_owned())
+}
+
+fn render(report: &Report, format: OutputFormat) -> String {
+    output::render(report, format).expect("only text is left to the caller")
+}
+
+fn note(message: &str) {
+    eprintln!("{} {}", Chalk::colorize("note:", 0x9FA1C9, true), message);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{GroupReport, ModuleReport, StatReport};
+    use std::sync::atomic::AtomicUsize;
+
+    fn report() -> Report {
+        let usage = StatReport {
+            name: "usage".to_string(),
+            value: 25.0.into(),
+            unit: Some("percent"),
+            text: "25.0%".to_string(),
+        };
+        Report {
+            version: output::REPORT_VERSION,
+            modules: vec![ModuleReport {
+                name: "CPU".to_string(),
+                status: "ok",
+                error: None,
+                groups: vec![GroupReport { title: None, stats: vec![usage] }],
+                plugin: false,
+            }],
+        }
+    }
+
+    fn get(address: std::net::SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn serves_on_localhost() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let stop = AtomicBool::new(false);
+        let collections = AtomicUsize::new(0);
+        thread::scope(|scope| {
+            let server = scope.spawn(|| {
+                serve(listener, Duration::from_secs(3600), &stop, || {
+                    collections.fetch_add(1, Ordering::Relaxed);
+                    report()
+                })
+            });
+            // a client that never sends its request holds up nobody
+            let _idle = TcpStream::connect(address).unwrap();
+            let started = Instant::now();
+
+            let health = get(address, "/healthz");
+            assert!(health.starts_with("HTTP/1.1 200 OK\r\n"), "{}", health);
+            assert!(health.ends_with("\r\n\r\nok\n"));
+            assert!(started.elapsed() < CLIENT_TIMEOUT);
+            assert_eq!(collections.load(Ordering::Relaxed), 0);
+
+            let json = get(address, "/json");
+            assert!(json.contains("Content-Type: application/json\r\n"));
+            let body: serde_json::Value = serde_json::from_str(json.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+            assert_eq!(body["modules"][0]["groups"][0]["stats"][0]["value"], 25.0);
+
+            let metrics = get(address, "/metrics");
+            assert!(metrics.contains("Content-Type: text/plain; version=0.0.4; charset=utf-8\r\n"));
+            assert!(metrics.ends_with("\nff_cpu_usage_ratio 0.25\n"), "{}", metrics);
+            assert_eq!(collections.load(Ordering::Relaxed), 1);
+
+            assert!(get(address, "/nothing").starts_with("HTTP/1.1 404 Not Found\r\n"));
+            stop.store(true, Ordering::Relaxed);
+            server.join().unwrap().unwrap();
+        });
+    }
+}